@@ -0,0 +1,8 @@
+import React from 'react'
+
+export const Demo = ({ wide }: { wide: boolean }) => (
+  <div className="h-12 w-12">
+    <span className={wide ? 'w-24' : 'w-12'}>{'class="not-used"'}</span>
+    <p className={`p-2`} {...rest} />
+  </div>
+)
@@ -1,6 +1,87 @@
+use crate::config::reg::import_path;
+use crate::replace::printer::Style;
 use serde_derive::*;
+use std::collections::HashMap;
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     pub reg: String,
     pub watch_dir: Vec<String>,
+    #[serde(default)]
+    pub rule_dirs: Vec<String>,
+    // inotify 或 poll, 不填使用当前平台默认的
+    #[serde(default)]
+    pub watcher: String,
+    // 相对于监听目录的 glob, 写法和 .gitignore 相同, include 为空时不限制
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default = "default_exclude")]
+    pub exclude: Vec<String>,
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+    // 同一文件在这段时间内的多次变化只处理一次, 单位毫秒
+    #[serde(default = "default_debounce")]
+    pub debounce: u64,
+    // 扩展名 => vue, html, jsx 或 svelte
+    #[serde(default = "default_extractors")]
+    pub extractors: HashMap<String, String>,
+    // 所有文件用到的类合并写入这个文件, 不填不生成
+    #[serde(default)]
+    pub bundle: String,
+    // 是否把 css 写回每个文件的 <style> 中
+    #[serde(default = "default_inject")]
+    pub inject: bool,
+    // 生成的 css 格式: plain, pretty 或 minified
+    #[serde(default)]
+    pub style: Style,
+    // 合并的样式文件同时生成 source map
+    #[serde(default)]
+    pub source_map: bool,
+}
+
+impl Config {
+    // watch_dir 和 bundle 与 reg 中的 @import 一样, 相对于配置文件所在的目录
+    pub fn relative_to(&mut self, config_path: &str) {
+        let from = Some(config_path.to_string());
+        for dir in self.watch_dir.iter_mut() {
+            *dir = import_path(dir, from.as_ref());
+        }
+        if !self.bundle.is_empty() {
+            self.bundle = import_path(&self.bundle, from.as_ref());
+        }
+    }
+}
+
+fn default_poll_interval() -> u64 {
+    500
+}
+
+fn default_debounce() -> u64 {
+    100
+}
+
+fn default_inject() -> bool {
+    true
+}
+
+fn default_exclude() -> Vec<String> {
+    vec!["node_modules/".to_string(), ".git/".to_string()]
+}
+
+fn default_extractors() -> HashMap<String, String> {
+    let mut rsl = HashMap::new();
+    rsl.insert("vue".to_string(), "vue".to_string());
+    rsl
+}
+
+#[test]
+fn test_relative_to() {
+    let mut config: Config = serde_json::from_str(
+        r#"{"reg": "", "watch_dir": ["./src", "/abs"], "bundle": "dist/a.css"}"#,
+    )
+    .unwrap();
+    config.relative_to("web/fcss.json");
+    assert_eq!(config.watch_dir, vec!["web/src", "/abs"]);
+    assert_eq!(config.bundle, "web/dist/a.css");
+    config.relative_to("fcss.json");
+    assert_eq!(config.watch_dir, vec!["web/src", "/abs"]);
 }
@@ -0,0 +1,92 @@
+use nom::error::{VerboseError, VerboseErrorKind};
+use std::error::Error;
+use std::fmt;
+
+// offset 所在的行和列, 都从1开始
+pub fn position(input: &str, offset: usize) -> (usize, usize) {
+    let before = &input[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = match before.rfind('\n') {
+        Some(p) => before[p + 1..].chars().count() + 1,
+        None => before.chars().count() + 1,
+    };
+    (line, column)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    pub file: String,
+    pub imported_from: Vec<String>,
+    pub line: usize,
+    pub column: usize,
+    pub snippet: String,
+    pub message: String,
+    pub context: Vec<String>,
+}
+
+impl ParseError {
+    pub fn new(input: &str, file: &str, rest: &str, message: String) -> Self {
+        let offset = input.len() - rest.len();
+        let (line, column) = position(input, offset);
+        let text = input.lines().nth(line - 1).unwrap_or("");
+        ParseError {
+            file: file.to_string(),
+            imported_from: vec![],
+            line,
+            column,
+            snippet: format!("{}\n{}^", text, " ".repeat(column - 1)),
+            message,
+            context: vec![],
+        }
+    }
+    pub fn from_nom(input: &str, file: &str, e: VerboseError<&str>) -> Self {
+        let (rest, message) = match e.errors.first() {
+            Some((rest, VerboseErrorKind::Char(c))) => (*rest, format!("expected '{}'", c)),
+            Some((rest, VerboseErrorKind::Nom(kind))) => {
+                (*rest, format!("unexpected input, {}", kind.description()))
+            }
+            Some((rest, VerboseErrorKind::Context(c))) => (*rest, format!("invalid {}", c)),
+            None => (input, "unexpected input".to_string()),
+        };
+        let mut rsl = ParseError::new(input, file, rest, message);
+        for (rest, kind) in &e.errors {
+            if let VerboseErrorKind::Context(c) = kind {
+                let (line, column) = position(input, input.len() - rest.len());
+                rsl.context.push(format!("{} at {}:{}", c, line, column));
+            }
+        }
+        rsl
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}:{}:{}: {}",
+            self.file, self.line, self.column, self.message
+        )?;
+        for line in self.snippet.lines() {
+            writeln!(f, "    {}", line)?;
+        }
+        for c in &self.context {
+            writeln!(f, "  in {}", c)?;
+        }
+        for from in &self.imported_from {
+            writeln!(f, "  imported from {}", from)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ParseError {}
+
+#[test]
+fn test_position() {
+    let input = "a{\n  width 1px;\n}";
+    assert_eq!(position(input, 0), (1, 1));
+    assert_eq!(position(input, 3), (2, 1));
+    assert_eq!(position(input, 10), (2, 8));
+    let e = ParseError::new(input, "a.reg", &input[10..], "expected ':'".to_string());
+    assert_eq!(e.snippet, "  width 1px;\n       ^");
+}
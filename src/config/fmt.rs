@@ -0,0 +1,99 @@
+use crate::config::reg::parse_source;
+use crate::pkg::result::CommonResult;
+
+const INDENT: &str = "    ";
+
+fn push_line(rsl: &mut String, depth: usize, line: &str) {
+    rsl.push_str(&INDENT.repeat(depth));
+    rsl.push_str(line);
+    rsl.push('\n');
+}
+// 选择器只合并空白, .a :hover 和 .a:hover 不同
+fn selector(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+// key:value 去掉多余的空白, ?extend 和 @import 只合并空白
+fn declaration(text: &str) -> String {
+    let text = selector(text);
+    match text.find(':') {
+        Some(p) if !text.starts_with('?') && !text.starts_with('@') => {
+            format!("{}:{}", text[..p].trim(), text[p + 1..].trim())
+        }
+        _ => text,
+    }
+}
+
+// 格式化规则文件, 保留注释和顶层的空行, 解析失败的文件不处理
+pub fn format(source: &str, file: &str) -> CommonResult<String> {
+    parse_source(source, file)?;
+    let mut rsl = String::new();
+    let mut depth = 0;
+    let mut pending = String::new();
+    let mut newlines = 0;
+    let mut chars = source.char_indices().peekable();
+    while let Some((idx, c)) = chars.next() {
+        if pending.trim().is_empty() {
+            if c == '\n' {
+                newlines += 1;
+                continue;
+            }
+            if c.is_whitespace() {
+                continue;
+            }
+            // 顶层的空行保留一行
+            if depth == 0 && newlines > 1 && !rsl.is_empty() {
+                rsl.push('\n');
+            }
+            newlines = 0;
+            if source[idx..].starts_with("//") {
+                let end = source[idx..].find('\n').map_or(source.len(), |p| idx + p);
+                push_line(&mut rsl, depth, source[idx..end].trim_end());
+                while chars.peek().is_some_and(|(i, _)| *i < end) {
+                    chars.next();
+                }
+                continue;
+            }
+        }
+        match c {
+            '{' => {
+                push_line(&mut rsl, depth, &format!("{}{{", selector(&pending)));
+                depth += 1;
+                pending.clear();
+            }
+            '}' => {
+                if !pending.trim().is_empty() {
+                    push_line(&mut rsl, depth, &format!("{};", declaration(&pending)));
+                }
+                depth = depth.saturating_sub(1);
+                push_line(&mut rsl, depth, "}");
+                pending.clear();
+            }
+            ';' => {
+                push_line(&mut rsl, depth, &format!("{};", declaration(&pending)));
+                pending.clear();
+            }
+            _ => pending.push(c),
+        }
+    }
+    Ok(rsl)
+}
+
+#[test]
+fn test_format() {
+    let source = "// sizes\n@import(./a.reg);\n.w-$1{width:$1px}\n\n\n\
+                  // nested\n.t-$1{\n  .c{\n    heigth : 10px;\n ?w-$1;\n           ?h-$1\n  }\n}\n";
+    let formatted = format(source, "a.reg").unwrap();
+    assert_eq!(
+        formatted,
+        "// sizes\n@import(./a.reg);\n.w-$1{\n    width:$1px;\n}\n\n\
+         // nested\n.t-$1{\n    .c{\n        heigth:10px;\n        ?w-$1;\n        ?h-$1;\n    }\n}\n"
+    );
+    assert_eq!(format(&formatted, "a.reg").unwrap(), formatted);
+    assert!(format(".a{", "a.reg").is_err());
+    // 选择器中的 : 不是声明
+    let source = ".a  :hover{color:red}\n.b   .c{\n.d  > .e{\ncolor : red;\n}\n}\n";
+    assert_eq!(
+        format(source, "a.reg").unwrap(),
+        ".a :hover{\n    color:red;\n}\n.b .c{\n    .d > .e{\n        color:red;\n    }\n}\n"
+    );
+}
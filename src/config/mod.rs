@@ -1,2 +1,4 @@
 pub mod config;
+pub mod error;
+pub mod fmt;
 pub mod reg;
@@ -1,181 +1,236 @@
+use crate::config::error::{position, ParseError};
+use crate::pkg::glob::{glob, is_glob, normalize};
 use crate::pkg::result::CommonResult;
-use crate::replace::signature::class_to_signature;
+use crate::replace::signature::signature;
 use nom::branch::alt;
 use nom::bytes::complete::{tag, take_while1};
-use nom::character::complete::{line_ending, multispace0, multispace1, none_of};
-use nom::combinator::{map, peek};
-use nom::error::context;
+use nom::character::complete::{char, multispace0, multispace1};
+use nom::combinator::{cut, map, peek};
+use nom::error::{context, VerboseError};
 use nom::multi::separated_list1;
 use nom::sequence::{delimited, preceded, separated_pair, terminated};
-use nom::{dbg_dmp, IResult};
+use nom::IResult;
+use serde_derive::Serialize;
 use std::collections::{HashMap, HashSet};
-use std::error::Error;
-use std::fs::File;
-use std::io::Read;
+use std::fs;
+use std::path::Path;
+
+// 节点在规则文件中的位置, 解析时 offset 先记录剩余输入的长度, parse_source 再换算成行列
+#[derive(Debug, PartialEq, Clone, Default, Serialize)]
+pub struct Span {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    #[serde(skip)]
+    offset: usize,
+}
+
+impl Span {
+    fn at(rest: &str) -> Self {
+        Span {
+            offset: rest.trim_start().len(),
+            ..Default::default()
+        }
+    }
+}
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum CSS {
-    Object(HashMap<String, CSS>),
+    Object(Vec<(String, CSS, Span)>),
     Value(String),
     ExtendValue(String),
     Import(String),
     Comment(String),
 }
+pub type Signatures = HashMap<String, Vec<(String, CSS, Span)>>;
+
 impl CSS {
-    pub fn get_signature(&self) -> CommonResult<HashMap<String, Vec<CSS>>> {
-        let mut rsl: HashMap<String, Vec<CSS>> = Default::default();
-        match self {
-            CSS::Object(d) => {
-                for (p, c) in d {
-                    match c {
-                        CSS::Value(d) => {}
-                        CSS::ExtendValue(d) => {}
-                        CSS::Import(d) => {}
-                        _ => {
-                            let mut key = "".to_string();
-                            if p.contains("-") {
-                                let mut index = -1;
-                                key = p
-                                    .split("-")
-                                    .map(|d| {
-                                        index += 1;
-                                        if index == 0 {
-                                            return d.to_string();
-                                        } else {
-                                            return format!("${}", index);
-                                        }
-                                    })
-                                    .collect::<Vec<String>>()
-                                    .join("-");
-                            } else {
-                                key = p.clone();
-                            }
-                            match rsl.get_mut(&key) {
-                                Some(v) => v.push(c.clone()),
-                                None => {
-                                    rsl.insert(key, vec![c.clone()]);
-                                    ()
-                                }
-                            };
-                        }
-                    }
+    // 签名 => [(规则选择器, 规则内容)], 同一签名可能对应多条规则, 如 .ba-(1)-(2) 和 .ba-(2)-(1)
+    pub fn get_signature(&self) -> CommonResult<Signatures> {
+        let mut rsl: Signatures = Default::default();
+        if let CSS::Object(d) = self {
+            for (p, c, span) in d {
+                if let CSS::Object(_) = c {
+                    rsl.entry(signature(p))
+                        .or_default()
+                        .push((p.clone(), c.clone(), span.clone()));
                 }
             }
-            _ => {}
         };
-        return Ok(rsl);
+        Ok(rsl)
     }
     pub fn have_import(&self) -> bool {
-        let mut r = false;
         match self {
-            CSS::Object(d) => {
-                for (p, c) in d {
-                    match c {
-                        CSS::Import(i) => {
-                            r = true;
-                            break;
-                        }
-                        _ => {}
+            CSS::Object(d) => d.iter().any(|(_, c, _)| matches!(c, CSS::Import(_))),
+            _ => false,
+        }
+    }
+    pub fn extend_import(&mut self) -> CommonResult<()> {
+        self.extend_import_from(&mut Default::default(), &mut vec![], &[])
+    }
+    // 每个 import 的文件单独解析再合并进来, 出错时可以知道是哪个文件
+    fn extend_import_from(
+        &mut self,
+        loaded_import: &mut HashSet<String>,
+        chain: &mut Vec<String>,
+        rule_dirs: &[String],
+    ) -> CommonResult<()> {
+        let d = match self {
+            CSS::Object(d) => d,
+            _ => return Ok(()),
+        };
+        let mut rsl = vec![];
+        for (k, v, span) in d.drain(..) {
+            let paths = match v {
+                CSS::Import(path) => import_paths(&path, chain.last(), rule_dirs)?,
+                _ => {
+                    rsl.push((k, v, span));
+                    continue;
+                }
+            };
+            for path in paths {
+                let key = canonical(&path);
+                if let Some(p) = chain.iter().position(|c| canonical(c) == key) {
+                    let mut cycle = chain[p..].to_vec();
+                    cycle.push(path);
+                    return Err(Box::from(format!("import cycle: {}", cycle.join(" -> "))));
+                }
+                // 同一个文件只引入一次, A 引入 B 和 C, B 和 C 都引入 D 时 D 只展开一次
+                if !loaded_import.insert(key) {
+                    continue;
+                }
+                let body = fs::read_to_string(&path).map_err(|e| {
+                    let mut msg = format!("{}: {}", path, e);
+                    for from in chain.iter().rev() {
+                        msg = format!("{}\n  imported from {}", msg, from);
                     }
+                    msg
+                })?;
+                let mut css = parse_source(&body, &path).map_err(|mut e| {
+                    e.imported_from = chain.iter().rev().cloned().collect();
+                    e
+                })?;
+                chain.push(path);
+                css.extend_import_from(loaded_import, chain, rule_dirs)?;
+                chain.pop();
+                if let CSS::Object(children) = css {
+                    rsl.extend(children);
                 }
             }
-            _ => {}
-        };
-        r
-    }
-    pub fn extend_import(&mut self) -> CommonResult<()> {
-        let mut loaded_import = Default::default();
-        while self.have_import() {
-            let format_css = self.get_import_css_str(&mut loaded_import)?;
-            let (_, mut data) = parse(format_css.as_str()).ok().unwrap();
-            *self = data;
         }
+        *d = rsl;
         Ok(())
     }
     pub fn to_string(&self) -> CommonResult<String> {
         let mut rsl = Default::default();
-        match self {
-            CSS::Object(d) => {
-                for (k, v) in d {
-                    match v {
-                        CSS::Value(d) => {
-                            rsl = format!("{}{}:{};{}", rsl, k, d, '\n');
-                        }
-                        CSS::ExtendValue(d) => {
-                            rsl = format!("{}?{};{}", rsl, k, '\n');
-                        }
-                        CSS::Import(d) => {
-                            rsl = format!("{}@import({}){}", rsl, d, '\n');
-                        }
-                        _ => {
-                            rsl = format!(
-                                "{}{}{}{}{}{}{}",
-                                rsl,
-                                k,
-                                "{",
-                                '\n',
-                                v.to_string()?,
-                                "}",
-                                '\n'
-                            )
-                        }
+        if let CSS::Object(d) = self {
+            for (k, v, _) in d {
+                match v {
+                    CSS::Value(d) => {
+                        rsl = format!("{}{}:{};{}", rsl, k, d, '\n');
+                    }
+                    CSS::ExtendValue(_) => {
+                        rsl = format!("{}?{};{}", rsl, k, '\n');
+                    }
+                    CSS::Import(d) => {
+                        rsl = format!("{}@import({}){}", rsl, d, '\n');
+                    }
+                    _ => {
+                        rsl = format!(
+                            "{}{}{}{}{}{}{}",
+                            rsl,
+                            k,
+                            "{",
+                            '\n',
+                            v.to_string()?,
+                            "}",
+                            '\n'
+                        )
                     }
                 }
             }
-            _ => {}
-        };
+        }
         Ok(rsl)
     }
-    fn get_import_css_str(&self, loaded_import: &mut HashSet<String>) -> CommonResult<String> {
-        let mut rsl = Default::default();
-        let mut import_str = Default::default();
-        match self {
-            CSS::Object(d) => {
-                for (k, v) in d {
-                    match v {
-                        CSS::Value(d) => {
-                            rsl = format!("{}{}:{};{}", rsl, k, d, '\n');
-                        }
-                        CSS::ExtendValue(d) => {
-                            rsl = format!("{}?{};{}", rsl, k, '\n');
-                        }
-                        CSS::Import(d) => {
-                            if loaded_import.contains(d) {
-                                return Err(Box::from(format!("{} loop import !", d)));
-                            }
-                            let mut import_file = File::open(d)?;
-                            let mut file_body = Default::default();
-                            import_file.read_to_string(&mut file_body)?;
-                            import_str = format!("  {}  {}\n", import_str, file_body);
-                            loaded_import.insert(d.clone());
-                        }
-                        _ => {
-                            rsl = format!(
-                                "{}{}{}{}{}{}{}",
-                                rsl,
-                                k,
-                                "{",
-                                '\n',
-                                v.to_string()?,
-                                "}",
-                                '\n'
-                            )
-                        }
-                    }
-                }
+    fn locate(&mut self, input: &str, file: &str) {
+        if let CSS::Object(d) = self {
+            for (_, v, span) in d {
+                let (line, column) = position(input, input.len() - span.offset);
+                span.file = file.to_string();
+                span.line = line;
+                span.column = column;
+                v.locate(input, file);
             }
-            _ => {}
-        };
-        rsl = format!("{}{}", import_str, rsl);
-        Ok(rsl)
+        }
+    }
+}
+// 文件的唯一标识, ./a.reg 和 a.reg 是同一个文件
+fn canonical(path: &str) -> String {
+    match fs::canonicalize(path) {
+        Ok(p) => p.to_string_lossy().to_string(),
+        Err(_) => normalize(Path::new(path)),
+    }
+}
+// 相对路径按引入它的文件所在目录解析
+pub fn import_path(path: &str, from: Option<&String>) -> String {
+    let path = Path::new(path);
+    match from.and_then(|f| Path::new(f).parent()) {
+        Some(dir) if path.is_relative() => normalize(&dir.join(path)),
+        _ => normalize(path),
+    }
+}
+// 支持 glob 和在规则目录中查找的名字, 如 @import(./rules/*.reg); @import(spacing);
+fn import_paths(
+    path: &str,
+    from: Option<&String>,
+    rule_dirs: &[String],
+) -> CommonResult<Vec<String>> {
+    let full = import_path(path, from);
+    if is_glob(path) {
+        let rsl = glob(&full)?;
+        if rsl.is_empty() {
+            return Err(Box::from(format!("no rule file matches {}", full)));
+        }
+        return Ok(rsl);
+    }
+    let bare = !path.starts_with('.') && !path.contains('/') && !path.contains('\\');
+    if !bare || Path::new(&full).is_file() || rule_dirs.is_empty() {
+        return Ok(vec![full]);
+    }
+    for dir in rule_dirs {
+        for name in &[path.to_string(), format!("{}.reg", path)] {
+            let candidate = Path::new(dir).join(name);
+            if candidate.is_file() {
+                return Ok(vec![normalize(&candidate)]);
+            }
+        }
+    }
+    Err(Box::from(format!(
+        "{} not found in rule dirs {}",
+        path,
+        rule_dirs.join(", ")
+    )))
+}
+
+type Res<'a, T> = IResult<&'a str, T, VerboseError<&'a str>>;
+
+// 记录节点开始的位置
+fn spanned<'a, K>(
+    mut f: impl FnMut(&'a str) -> Res<'a, (K, CSS)>,
+) -> impl FnMut(&'a str) -> Res<'a, (K, CSS, Span)> {
+    move |i: &'a str| {
+        let span = Span::at(i);
+        let (i, (k, v)) = f(i)?;
+        Ok((i, (k, v, span)))
     }
 }
-fn comment(i: &str) -> IResult<&str, (String, CSS)> {
+
+fn comment(i: &str) -> Res<'_, (String, CSS)> {
     let (i, rsp) = take_while1(|c| c != '\n')(i)?;
     tag("//")(rsp)?;
     Ok((i, ("".to_string(), CSS::Comment(rsp.trim().to_string()))))
 }
-fn import(i: &str) -> IResult<&str, (String, CSS)> {
+fn import(i: &str) -> Res<'_, (String, CSS)> {
     let (i, rsp) = take_while1(|c| c != ':' && c != ';' && c != '}' && c != '{')(i)?;
     let (rsp, _) = tag("@import(")(rsp)?;
     let (_, rsp) = take_while1(|c| c != ')')(rsp)?;
@@ -186,10 +241,10 @@ fn import(i: &str) -> IResult<&str, (String, CSS)> {
     ))
 }
 // 有些是;之后是需要消除的
-fn end(i: &str) -> IResult<&str, &str> {
+fn end(i: &str) -> Res<'_, &str> {
     delimited(tag(";"), preceded(multispace0, peek(tag("}"))), multispace0)(i)
 }
-fn extend(i: &str) -> IResult<&str, (&str, CSS)> {
+fn extend(i: &str) -> Res<'_, (&str, CSS)> {
     let (i, _) = multispace0(i)?;
     let (i, _) = tag("?")(i)?;
     let (i, rsp) = take_while1(|c| c != ':' && c != ';' && c != '}')(i)?;
@@ -199,11 +254,20 @@ fn extend(i: &str) -> IResult<&str, (&str, CSS)> {
     };
     Ok((i, (rsp.trim(), CSS::ExtendValue(rsp.trim().to_string()))))
 }
-fn key(i: &str) -> IResult<&str, &str> {
-    let (i, rsp) = take_while1(|c| c != ':' && c != '}' && c != '{')(i)?;
+fn key(i: &str) -> Res<'_, &str> {
+    let (i, rsp) = take_while1(|c| c != ':' && c != ';' && c != '}' && c != '{')(i)?;
     Ok((i, rsp.trim()))
 }
-fn value(i: &str) -> IResult<&str, CSS> {
+// key 后面是 ; 或者 } 时一定是声明, 缺少 : 直接报错在这一行, 不再当作嵌套的规则去解析
+fn declaration(i: &str) -> Res<'_, (&str, CSS)> {
+    let (rest, k) = key(i)?;
+    let is_node = k.is_empty() || k.starts_with('@') || k.starts_with("//");
+    if is_node || !rest.starts_with(&[';', '}'][..]) {
+        return separated_pair(key, char(':'), value)(i);
+    }
+    context("declaration", separated_pair(key, cut(char(':')), value))(i)
+}
+fn value(i: &str) -> Res<'_, CSS> {
     let (i, rsp) = take_while1(|c| c != ';' && c != '}' && c != '{')(i)?;
     // 判断是不是结束
     if let Ok((i, _)) = end(i) {
@@ -211,23 +275,26 @@ fn value(i: &str) -> IResult<&str, CSS> {
     }
     Ok((i, CSS::Value(rsp.trim().to_string())))
 }
-fn selector(i: &str) -> IResult<&str, String> {
+fn selector(i: &str) -> Res<'_, String> {
     let (i, rsp) = take_while1(|c| c != '{' && c != '}')(i)?;
     // 判断是否是key
     Ok((i, rsp.trim().to_string()))
 }
-fn object(i: &str) -> IResult<&str, CSS> {
+fn object(i: &str) -> Res<'_, CSS> {
     context(
         "object",
         alt((
             delimited(
                 multispace0,
                 map(
-                    separated_list1(
-                        tag(";"),
-                        alt((extend, separated_pair(key, tag(":"), value))),
-                    ),
-                    |d| CSS::Object(d.into_iter().map(|(k, v)| (k.to_string(), v)).collect()),
+                    separated_list1(char(';'), alt((spanned(extend), spanned(declaration)))),
+                    |d| {
+                        CSS::Object(
+                            d.into_iter()
+                                .map(|(k, v, span)| (k.to_string(), v, span))
+                                .collect(),
+                        )
+                    },
                 ),
                 multispace0,
             ),
@@ -235,32 +302,75 @@ fn object(i: &str) -> IResult<&str, CSS> {
         )),
     )(i)
 }
-pub fn parse(i: &str) -> IResult<&str, CSS> {
+fn node(i: &str) -> Res<'_, (String, CSS, Span)> {
+    spanned(alt((
+        comment,
+        import,
+        separated_pair(selector, char('{'), terminated(object, char('}'))),
+    )))(i)
+}
+pub fn parse(i: &str) -> Res<'_, CSS> {
     context(
         "node",
         delimited(
             multispace0,
-            map(
-                separated_list1(
-                    multispace1,
-                    alt((
-                        comment,
-                        import,
-                        separated_pair(selector, tag("{"), terminated(object, tag("}"))),
-                    )),
-                ),
-                |d| CSS::Object(d.into_iter().filter(|(k, v)| !k.is_empty()).collect()),
-            ),
+            map(separated_list1(multispace1, node), |d| {
+                CSS::Object(d.into_iter().filter(|(k, _, _)| !k.is_empty()).collect())
+            }),
             multispace0,
         ),
     )(i)
 }
+// 解析整个文件, 没有解析完的部分也作为错误返回
+pub fn parse_source(i: &str, file: &str) -> Result<CSS, Box<ParseError>> {
+    if i.trim().is_empty() {
+        return Ok(CSS::Object(vec![]));
+    }
+    let e = match parse(i) {
+        Ok((rest, mut css)) if rest.trim().is_empty() => {
+            css.locate(i, file);
+            return Ok(css);
+        }
+        Ok((rest, _)) => match context("node", node)(rest.trim_start()) {
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => e,
+            _ => {
+                return Err(Box::new(ParseError::new(
+                    i,
+                    file,
+                    rest.trim_start(),
+                    "unexpected input".to_string(),
+                )))
+            }
+        },
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => e,
+        Err(nom::Err::Incomplete(_)) => {
+            return Err(Box::new(ParseError::new(
+                i,
+                file,
+                "",
+                "unexpected end of input".to_string(),
+            )))
+        }
+    };
+    Err(Box::new(ParseError::from_nom(i, file, e)))
+}
+// 解析并展开所有的 import, rule_dirs 相对于 file 所在目录
+pub fn load(i: &str, file: &str, rule_dirs: &[String]) -> CommonResult<CSS> {
+    let file = file.to_string();
+    let rule_dirs = rule_dirs
+        .iter()
+        .map(|d| import_path(d, Some(&file)))
+        .collect::<Vec<String>>();
+    let mut css = parse_source(i, &file)?;
+    css.extend_import_from(&mut Default::default(), &mut vec![file], &rule_dirs)?;
+    Ok(css)
+}
 #[test]
 fn test_css_parse() {
     let (_, mut data) = parse(
         "
         // @import(./test1);
-        @import( /home/jiuker/rustworkspace/fcss/res/test/reg/test.reg );
+        @import( ./res/test/reg/test.reg );
         .x a{
             .x{
                 .x{
@@ -327,18 +437,153 @@ fn test_css_parse() {
         .unwrap()
         .into_bytes()
         .into_iter()
-        .filter(|c| *c == '?' as u8)
+        .filter(|c| *c == b'?')
         .count();
-    // 是否全部解析import成功
-    assert_eq!(c, 19);
+    // 是否全部解析import成功, 这里 11 个, test.reg 8 个, test1.reg 4 个
+    assert_eq!(c, 23);
+    // test.reg 中的 @import(./test1.reg) 相对于 test.reg 解析, 两个文件的 .t 规则都在
+    let signatures = data.get_signature().unwrap();
+    assert_eq!(signatures[".t-$1-$2"].len(), 2);
+    assert!(signatures.contains_key(".c-$1-$2"));
+    assert!(signatures.contains_key(".e2"));
     // 测试循环检测是否成功
-    let (_, mut data) = parse("@import( /home/jiuker/rustworkspace/fcss/res/test/reg/loop.reg );")
-        .ok()
-        .unwrap();
+    let (_, mut data) = parse("@import( ./res/test/reg/loop.reg );").ok().unwrap();
     if let Err(e) = data.extend_import() {
         dbg!(e);
     } else {
         panic!("shouldn't")
     }
-    dbg!(data.get_signature());
+    dbg!(data.get_signature().unwrap());
+}
+#[test]
+fn test_css_order() {
+    let (_, data) = parse(
+        "
+        .x{
+            width:1px;
+            height:2px;
+            width:3px;
+        }
+        .a{
+            color:red;
+        }
+        .x{
+            width:4px;
+        }
+    ",
+    )
+    .ok()
+    .unwrap();
+    assert_eq!(
+        data.to_string().unwrap(),
+        ".x{\nwidth:1px;\nheight:2px;\nwidth:3px;\n}\n.a{\ncolor:red;\n}\n.x{\nwidth:4px;\n}\n"
+    );
+    assert_eq!(data.get_signature().unwrap()[".x"].len(), 2);
+}
+#[test]
+fn test_parse_error() {
+    let e = parse_source(".a{\n    width:1px;\n}\n.b{\n    width 1px;\n}\n", "a.reg").unwrap_err();
+    assert_eq!((e.file.as_str(), e.line, e.column), ("a.reg", 5, 14));
+    assert_eq!(e.message, "expected ':'");
+    assert_eq!(e.snippet, "    width 1px;\n             ^");
+    assert!(e.context.iter().any(|c| c == "declaration at 5:5"));
+    assert!(e.context.iter().any(|c| c.starts_with("object")));
+    let e = parse_source(".a{\n    width:1px;\n", "a.reg").unwrap_err();
+    assert_eq!((e.line, e.column), (2, 14));
+    assert_eq!(e.message, "expected '}'");
+    assert!(parse_source("\n", "a.reg").is_ok());
+    let e = load("@import(./res/test/reg/not_found.reg);", "config.json", &[]).unwrap_err();
+    assert!(e.to_string().contains("imported from config.json"));
+}
+#[test]
+fn test_relative_import() {
+    let config_path = "./res/test/config.json";
+    let mut config: crate::config::config::Config =
+        serde_json::from_reader(fs::File::open(config_path).unwrap()).unwrap();
+    config.relative_to(config_path);
+    assert_eq!(config.watch_dir, vec!["res/test/vue"]);
+    assert!(Path::new(&config.watch_dir[0]).is_dir());
+    let css = load(&config.reg, config_path, &config.rule_dirs).unwrap();
+    let signatures = css.get_signature().unwrap();
+    assert_eq!(signatures[".t-$1-$2"].len(), 2);
+    assert!(signatures.contains_key(".c-$1-$2"));
+    assert_eq!(
+        import_path("./b.reg", Some(&"rules/a.reg".to_string())),
+        "rules/b.reg"
+    );
+    assert_eq!(
+        import_path("/x/b.reg", Some(&"rules/a.reg".to_string())),
+        "/x/b.reg"
+    );
+    assert_eq!(import_path("./b.reg", None), "b.reg");
+}
+#[test]
+fn test_glob_import() {
+    let css = load(
+        "@import(./reg/rules/*.reg);\n@import(spacing);",
+        "./res/test/config.json",
+        &["./lib".to_string()],
+    )
+    .unwrap();
+    let selectors = match css {
+        CSS::Object(d) => d.into_iter().map(|(k, _, _)| k).collect::<Vec<String>>(),
+        _ => vec![],
+    };
+    assert_eq!(selectors, vec![".c-$1", ".w-$1", ".h-$1", ".p-$1", ".m-$1"]);
+    let e = load(
+        "@import(spacing);",
+        "./res/test/config.json",
+        &["./reg".to_string()],
+    )
+    .unwrap_err();
+    assert_eq!(e.to_string(), "spacing not found in rule dirs res/test/reg");
+    assert!(load("@import(./reg/none/*.reg);", "./res/test/config.json", &[]).is_err());
+}
+#[test]
+fn test_import_once() {
+    let css = load(
+        "@import(./reg/diamond/a.reg);\n@import(reg/diamond/d.reg);",
+        "./res/test/config.json",
+        &[],
+    )
+    .unwrap();
+    let selectors = match css {
+        CSS::Object(d) => d.into_iter().map(|(k, _, _)| k).collect::<Vec<String>>(),
+        _ => vec![],
+    };
+    assert_eq!(selectors, vec![".d-$1", ".b-$1", ".c-$1", ".a-$1"]);
+    let (_, mut data) = parse("@import(./res/test/reg/loop.reg);").ok().unwrap();
+    assert_eq!(
+        data.extend_import().unwrap_err().to_string(),
+        "import cycle: res/test/reg/loop.reg -> res/test/reg/loop1.reg -> res/test/reg/loop.reg"
+    );
+}
+#[test]
+fn test_span() {
+    let css = parse_source("// a\n.a{\n    width:1px;\n    ?w-1\n}\n", "a.reg").unwrap();
+    let rules = css.get_signature().unwrap();
+    let (_, body, span) = &rules[".a"][0];
+    assert_eq!(
+        (span.file.as_str(), span.line, span.column),
+        ("a.reg", 2, 1)
+    );
+    let spans = match body {
+        CSS::Object(d) => d
+            .iter()
+            .map(|(_, _, s)| (s.line, s.column))
+            .collect::<Vec<_>>(),
+        _ => vec![],
+    };
+    assert_eq!(spans, vec![(3, 5), (4, 5)]);
+    let css = load(
+        "@import(./reg/rules/size.reg);",
+        "./res/test/config.json",
+        &[],
+    )
+    .unwrap();
+    let (_, _, span) = &css.get_signature().unwrap()[".h-$1"][0];
+    assert_eq!(
+        (span.file.as_str(), span.line),
+        ("res/test/reg/rules/size.reg", 4)
+    );
 }
@@ -1,51 +1,597 @@
-use clap::{App, Arg};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use fcss::config::config::Config;
-use fcss::config::reg::parse;
-use fcss::pkg::dir::walk_all_dir;
-use fcss::watch::watch::Watch;
-use inotify::{EventMask, Inotify, WatchMask};
-use std::fs::File;
-use std::io::Read;
+use fcss::config::fmt::format;
+use fcss::config::reg::{load, Signatures};
+use fcss::pkg::ignore::Filter;
+use fcss::pkg::result::CommonResult;
+use fcss::replace::build::{build, check};
+use fcss::replace::bundle::Bundle;
+use fcss::replace::extractor::{replace, Extractors};
+use fcss::replace::printer::print;
+use fcss::replace::resolve::{explain_class, similar};
+use fcss::replace::signature::signature;
+use fcss::watch::watch::{backend, EventKind, Watch};
+use serde_json::json;
+use std::fs::{self, File};
+use std::io::IsTerminal;
+use std::path::Path;
 use std::sync::Arc;
 use std::thread::spawn;
+use std::time::Duration;
 
-fn main() {
-    let matches = App::new("fcss")
+// 成功, 检查没有通过, 配置或运行出错
+const EXIT_OK: i32 = 0;
+const EXIT_FAILED: i32 = 1;
+const EXIT_ERROR: i32 = 2;
+
+const DEFAULT_CONFIG: &str = r#"{
+  "reg": "@import(./rules/*.reg);",
+  "watch_dir": ["./src"]
+}"#;
+
+const DEFAULT_RULES: &str = "// $1 为类名中的参数, 如 .w-12 生成 width:12px
+.w-$1{
+    width:$1px;
+}
+.h-$1{
+    height:$1px;
+}
+";
+
+fn app() -> App<'static, 'static> {
+    App::new("fcss")
         .version("1.0.1")
         .author("jiuker")
+        .about("Generates css from class names, runs watch when no subcommand is given")
+        .setting(AppSettings::VersionlessSubcommands)
         .arg(
             Arg::with_name("config")
                 .short("c")
                 .long("config")
                 .value_name("FILE")
+                .default_value("fcss.json")
                 .help("Sets a config file")
-                .takes_value(true),
-        )
-        .get_matches();
-    let config_path = matches
-        .value_of("config")
-        .unwrap_or("./res/test/config.json");
-    println!("load config {} file", config_path);
-    let config: Config;
-    {
-        config = serde_json::from_reader(File::open(config_path).unwrap()).unwrap();
-    }
-    println!("config {:?}", config);
-    println!("ready to load css!");
-    let (_, mut css) = parse(config.reg.as_str()).ok().unwrap();
-    css.extend_import().unwrap();
-    println!("load css:{}", css.to_string().unwrap());
-    println!("load css match {:?}", css.get_signature().unwrap().keys());
-    let watch = Arc::new(Watch::new("vue".to_string()));
-    for dir in config.watch_dir {
-        watch.add(dir);
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .multiple(true)
+                .help("Prints debug information")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .help("Prints errors only")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("color")
+                .long("color")
+                .value_name("WHEN")
+                .possible_values(&["auto", "always", "never"])
+                .default_value("auto")
+                .help("Colors the output")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("style")
+                .long("style")
+                .value_name("STYLE")
+                .possible_values(&["plain", "pretty", "minified"])
+                .help("Sets the format of generated css, overrides the config")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .help("Prints results as json")
+                .global(true),
+        )
+        .subcommand(SubCommand::with_name("watch").about("Regenerates css when files change"))
+        .subcommand(
+            SubCommand::with_name("build")
+                .about("Generates css for every file in watch_dir and exits"),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Reports classes without a matching rule, writes nothing"),
+        )
+        .subcommand(
+            SubCommand::with_name("explain")
+                .about("Shows how a class is resolved")
+                .arg(
+                    Arg::with_name("class")
+                        .help("Class name, such as w-12")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("init")
+                .about("Creates a config file and a sample rule file")
+                .arg(
+                    Arg::with_name("force")
+                        .short("f")
+                        .long("force")
+                        .help("Overwrites the existing config file"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("fmt")
+                .about("Formats rule files, keeping comments")
+                .arg(
+                    Arg::with_name("file")
+                        .help("Rule files to format")
+                        .required(true)
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("check")
+                        .long("check")
+                        .help("Reports unformatted files without writing them"),
+                ),
+        )
+}
+
+struct Output {
+    verbose: u64,
+    quiet: bool,
+    color: bool,
+    json: bool,
+}
+
+impl Output {
+    fn new(matches: &ArgMatches) -> Self {
+        let color = match matches.value_of("color") {
+            Some("always") => true,
+            Some("never") => false,
+            _ => std::io::stderr().is_terminal(),
+        };
+        Output {
+            verbose: matches.occurrences_of("verbose"),
+            quiet: matches.is_present("quiet"),
+            color,
+            json: matches.is_present("json"),
+        }
+    }
+    fn paint(&self, code: &str, s: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", code, s)
+        } else {
+            s.to_string()
+        }
+    }
+    // --quiet 和 --json 时不输出
+    fn info(&self, msg: &str) {
+        if !self.quiet && !self.json {
+            println!("{}", msg);
+        }
+    }
+    fn debug(&self, msg: &str) {
+        if self.verbose > 0 && !self.json {
+            eprintln!("{}", msg);
+        }
+    }
+    fn warn(&self, msg: &str) {
+        if !self.quiet {
+            eprintln!("{} {}", self.paint("33", "warning:"), msg);
+        }
+    }
+    fn error(&self, msg: &str) {
+        eprintln!("{} {}", self.paint("31", "error:"), msg);
+    }
+    fn json(&self, value: serde_json::Value) {
+        println!("{}", value);
+    }
+}
+
+struct Context {
+    config: Config,
+    signatures: Signatures,
+    filter: Filter,
+    extractors: Extractors,
+}
+
+fn load_context(config_path: &str, out: &Output) -> Result<Context, String> {
+    out.debug(&format!("load config {}", config_path));
+    let file = File::open(config_path).map_err(|e| format!("{}: {}", config_path, e))?;
+    let mut config: Config =
+        serde_json::from_reader(file).map_err(|e| format!("{}: {}", config_path, e))?;
+    config.relative_to(config_path);
+    out.debug(&format!("config {:?}", config));
+    let css = load(&config.reg, config_path, &config.rule_dirs)
+        .map_err(|e| format!("load css error: {}", e))?;
+    let signatures = css.get_signature().map_err(|e| e.to_string())?;
+    out.debug(&format!("load css match {:?}", signatures.keys()));
+    let filter = Filter::new(&config.include, &config.exclude)
+        .map_err(|e| format!("init filter error: {}", e))?;
+    let extractors =
+        Extractors::new(&config.extractors).map_err(|e| format!("init extractors error: {}", e))?;
+    Ok(Context {
+        config,
+        signatures,
+        filter,
+        extractors,
+    })
+}
+
+fn main() {
+    let matches = app().get_matches();
+    // 全局参数写在子命令后面时只在子命令中
+    let global = match matches.subcommand() {
+        (_, Some(m)) => m,
+        _ => &matches,
+    };
+    let out = Output::new(global);
+    let config_path = global.value_of("config").unwrap_or("fcss.json");
+    let code = match matches.subcommand() {
+        ("init", Some(m)) => run_init(config_path, m, &out),
+        ("fmt", Some(m)) => run_fmt(m, &out),
+        (name, m) => match load_context(config_path, &out) {
+            Err(e) => {
+                out.error(&e);
+                EXIT_ERROR
+            }
+            Ok(mut ctx) => {
+                if let Some(style) = global.value_of("style") {
+                    ctx.config.style = style.parse().unwrap();
+                }
+                match (name, m) {
+                    ("build", _) => run_build(&ctx, &out),
+                    ("check", _) => run_check(&ctx, &out),
+                    ("explain", Some(m)) => run_explain(&ctx, m.value_of("class").unwrap(), &out),
+                    _ => run_watch(ctx, &out),
+                }
+            }
+        },
+    };
+    std::process::exit(code);
+}
+
+fn run_watch(ctx: Context, out: &Output) -> i32 {
+    let watcher = match backend(
+        &ctx.config.watcher,
+        &ctx.extractors.extensions(),
+        ctx.config.poll_interval,
+        ctx.filter.clone(),
+    ) {
+        Ok(w) => w,
+        Err(e) => {
+            out.error(&format!("init watcher error: {}", e));
+            return EXIT_ERROR;
+        }
+    };
+    let watch = Arc::new(Watch::new(
+        watcher,
+        Duration::from_millis(ctx.config.debounce),
+    ));
+    for dir in &ctx.config.watch_dir {
+        if let Err(e) = watch.add(dir.clone()) {
+            out.error(&format!("watch {} error: {}", dir, e));
+            return EXIT_ERROR;
+        }
+        out.debug(&format!("watch {}", dir));
     }
     let w_c = watch.clone();
     spawn(move || {
-        w_c.watch();
+        if let Err(e) = w_c.watch() {
+            eprintln!("watch error: {}", e);
+            std::process::exit(EXIT_ERROR);
+        }
+    });
+    // 启动时先扫描所有文件生成合并的样式文件, 之后只处理变化的文件
+    let mut bundle = match ctx.config.bundle.as_str() {
+        "" => None,
+        path => Some(Bundle::new(path, ctx.config.style, ctx.config.source_map)),
+    };
+    if let Some(b) = bundle.as_mut() {
+        let report = build(
+            &ctx.config.watch_dir,
+            &ctx.filter,
+            &ctx.extractors,
+            &ctx.signatures,
+            ctx.config.style,
+            false,
+            Some(b),
+        );
+        match report {
+            Ok(report) => {
+                for (p, e) in &report.errors {
+                    out.error(&format!("{}: {}", p, e));
+                }
+            }
+            Err(e) => {
+                out.error(&format!("bundle error: {}", e));
+                return EXIT_ERROR;
+            }
+        }
+    }
+    while let Ok(e) = watch.receiver.lock().unwrap().recv() {
+        let p = e.path.to_string_lossy();
+        let extractor = match ctx.extractors.get(&e.path) {
+            Some(x) => x,
+            None => {
+                out.debug(&format!("{} {:?}", p, e.kind));
+                continue;
+            }
+        };
+        let mut updated = vec![];
+        let mut errors = vec![];
+        if e.kind != EventKind::Remove && ctx.config.inject {
+            match replace(&p, extractor, &ctx.signatures, ctx.config.style) {
+                Ok(true) => updated.push(p.to_string()),
+                Ok(false) => {}
+                Err(err) => errors.push(format!("update {} error: {}", p, err)),
+            }
+        }
+        if let Some(b) = bundle.as_mut() {
+            let rsl = if e.kind == EventKind::Remove {
+                b.remove(&e.path);
+                Ok(())
+            } else {
+                b.update(&e.path, extractor, &ctx.signatures)
+            };
+            match rsl.and_then(|_| b.write()) {
+                Ok((true, _)) => updated.push(b.path().to_string_lossy().to_string()),
+                Ok(_) => {}
+                Err(err) => errors.push(format!("update {} error: {}", b.path().display(), err)),
+            }
+        }
+        if out.json {
+            out.json(json!({
+                "path": e.path,
+                "kind": e.kind,
+                "updated": updated,
+                "errors": errors,
+            }));
+            continue;
+        }
+        if updated.is_empty() && errors.is_empty() {
+            out.debug(&format!("{} not changed", p));
+        }
+        for u in &updated {
+            out.info(&format!("update {} success", u));
+        }
+        for err in &errors {
+            out.error(err);
+        }
+    }
+    EXIT_OK
+}
+
+// 有没有匹配到规则的类名也算失败
+fn run_build(ctx: &Context, out: &Output) -> i32 {
+    let mut bundle = match ctx.config.bundle.as_str() {
+        "" => None,
+        path => Some(Bundle::new(path, ctx.config.style, ctx.config.source_map)),
+    };
+    let report = match build(
+        &ctx.config.watch_dir,
+        &ctx.filter,
+        &ctx.extractors,
+        &ctx.signatures,
+        ctx.config.style,
+        ctx.config.inject,
+        bundle.as_mut(),
+    ) {
+        Ok(r) => r,
+        Err(e) => {
+            out.error(&format!("build error: {}", e));
+            return EXIT_ERROR;
+        }
+    };
+    if out.json {
+        out.json(json!(report));
+    } else {
+        for p in &report.written {
+            out.info(&format!("update {} success", p));
+        }
+        for (p, classes) in &report.unresolved {
+            out.warn(&format!("{}: unresolved class {}", p, classes.join(" ")));
+        }
+        for (p, e) in &report.errors {
+            out.error(&format!("{}: {}", p, e));
+        }
+        out.info(&format!(
+            "build {} files, {} updated, {} unresolved, {} errors",
+            report.files,
+            report.written.len(),
+            report.unresolved.len(),
+            report.errors.len()
+        ));
+    }
+    if !report.errors.is_empty() {
+        EXIT_ERROR
+    } else if !report.ok() {
+        EXIT_FAILED
+    } else {
+        EXIT_OK
+    }
+}
+
+// 有未知的类名时返回非 0
+fn run_check(ctx: &Context, out: &Output) -> i32 {
+    let report = match check(
+        &ctx.config.watch_dir,
+        &ctx.filter,
+        &ctx.extractors,
+        &ctx.signatures,
+    ) {
+        Ok(r) => r,
+        Err(e) => {
+            out.error(&format!("check error: {}", e));
+            return EXIT_ERROR;
+        }
+    };
+    if out.json {
+        out.json(json!(report));
+    } else {
+        for (p, unknown) in &report.unknown {
+            out.info(p);
+            for u in unknown {
+                out.info(&format!(
+                    "  {}:{} unknown class {}",
+                    u.line, u.column, u.class
+                ));
+            }
+        }
+        for (p, e) in &report.errors {
+            out.error(&format!("{}: {}", p, e));
+        }
+        out.info(&format!(
+            "check {} files, {} with unknown classes, {} errors",
+            report.files,
+            report.unknown.len(),
+            report.errors.len()
+        ));
+    }
+    if !report.errors.is_empty() {
+        EXIT_ERROR
+    } else if !report.ok() {
+        EXIT_FAILED
+    } else {
+        EXIT_OK
+    }
+}
+
+fn run_explain(ctx: &Context, class: &str, out: &Output) -> i32 {
+    let class = class.trim_start_matches('.');
+    let sig = signature(&format!(".{}", class));
+    let (steps, rules) = match explain_class(class, &ctx.signatures) {
+        Ok(Some(d)) => d,
+        Ok(None) => {
+            let similar = similar(class, &ctx.signatures);
+            if out.json {
+                out.json(json!({ "class": class, "signature": sig, "similar": similar }));
+            } else {
+                out.error(&format!("no rule matches {} ({})", class, sig));
+                if !similar.is_empty() {
+                    eprintln!("did you mean: {}", similar.join(", "));
+                }
+            }
+            return EXIT_FAILED;
+        }
+        Err(e) => {
+            if out.json {
+                out.json(json!({ "class": class, "signature": sig, "error": e.to_string() }));
+            } else {
+                out.error(&format!("{}: {}", class, e));
+            }
+            return EXIT_ERROR;
+        }
+    };
+    if out.json {
+        out.json(json!({
+            "class": class,
+            "signature": sig,
+            "steps": steps,
+            "rules": rules,
+        }));
+        return EXIT_OK;
+    }
+    println!("signature {}", sig);
+    for step in &steps {
+        let args = step
+            .args
+            .iter()
+            .map(|(i, v)| format!("${}={}", i, v))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let action = if step.depth == 0 { "match" } else { "extend" };
+        println!(
+            "{}{} {} => {} at {}:{} {}",
+            "  ".repeat(step.depth),
+            action,
+            step.selector,
+            step.pattern,
+            step.span.file,
+            step.span.line,
+            args
+        );
+    }
+    println!();
+    print!("{}", print(&rules, ctx.config.style));
+    EXIT_OK
+}
+
+fn run_init(config_path: &str, m: &ArgMatches, out: &Output) -> i32 {
+    if Path::new(config_path).exists() && !m.is_present("force") {
+        out.error(&format!(
+            "{} already exists, use --force to overwrite it",
+            config_path
+        ));
+        return EXIT_ERROR;
+    }
+    // 写出所有配置项和默认值
+    let config: Config = serde_json::from_str(DEFAULT_CONFIG).unwrap();
+    let config = serde_json::to_string_pretty(&config).unwrap();
+    let rules = Path::new(config_path)
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join("rules");
+    let base = rules.join("base.reg");
+    let written = fs::write(config_path, config + "\n").and_then(|_| {
+        if base.exists() {
+            return Ok(());
+        }
+        fs::create_dir_all(&rules)?;
+        fs::write(&base, DEFAULT_RULES)
     });
-    while let Ok(p) = watch.receiver.lock().unwrap().recv() {
-        println!("{}", p);
-        break;
+    if let Err(e) = written {
+        out.error(&format!("init error: {}", e));
+        return EXIT_ERROR;
+    }
+    out.info(&format!("created {} and {}", config_path, base.display()));
+    EXIT_OK
+}
+
+fn run_fmt(m: &ArgMatches, out: &Output) -> i32 {
+    let check_only = m.is_present("check");
+    let mut changed = vec![];
+    let mut errors = vec![];
+    for file in m.values_of("file").unwrap() {
+        let formatted: CommonResult<(String, String)> = fs::read_to_string(file)
+            .map_err(|e| e.into())
+            .and_then(|source| Ok((format(&source, file)?, source)));
+        match formatted {
+            Ok((formatted, source)) if formatted != source => {
+                if !check_only {
+                    if let Err(e) = fs::write(file, formatted) {
+                        errors.push(format!("{}: {}", file, e));
+                        continue;
+                    }
+                }
+                changed.push(file.to_string());
+            }
+            Ok(_) => {}
+            Err(e) => errors.push(format!("{}: {}", file, e)),
+        }
+    }
+    if out.json {
+        out.json(json!({ "changed": changed, "errors": errors }));
+    } else {
+        for file in &changed {
+            if check_only {
+                out.warn(&format!("{} is not formatted", file));
+            } else {
+                out.info(&format!("formatted {}", file));
+            }
+        }
+        for e in &errors {
+            out.error(e);
+        }
+    }
+    if !errors.is_empty() {
+        EXIT_ERROR
+    } else if check_only && !changed.is_empty() {
+        EXIT_FAILED
+    } else {
+        EXIT_OK
     }
 }
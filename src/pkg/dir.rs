@@ -1,51 +1,73 @@
+use crate::pkg::ignore::{Filter, Ignores};
 use crate::pkg::result::CommonResult;
-use std::collections::hash_map::RandomState;
 use std::collections::{HashMap, HashSet};
-use std::fs::read_dir;
-use std::ops::Add;
+use std::io;
+use std::path::Path;
 
-pub fn walk_all_dir(
-    path: &String,
-) -> CommonResult<(Vec<String>, HashMap<String, HashSet<String>>)> {
-    let current_list = std::fs::read_dir(path)?;
+// 文件名 => 所在的目录
+pub type FileDirs = HashMap<String, HashSet<String>>;
+
+// 遍历所有文件, 不受 .gitignore 影响
+pub fn walk_all_dir(path: &String) -> CommonResult<(Vec<String>, FileDirs)> {
+    walk_dir(Path::new(path), Path::new(path), &Filter::none())
+}
+
+// 遍历 root 下的 dir, 跳过被过滤的目录和文件, dir 本身被忽略时返回空
+pub fn walk_dir(root: &Path, dir: &Path, filter: &Filter) -> CommonResult<(Vec<String>, FileDirs)> {
     let mut dirs: Vec<String> = vec![];
-    let mut file_dirs: HashMap<String, HashSet<String>> = Default::default();
-    for entry in current_list {
+    let mut file_dirs: FileDirs = Default::default();
+    if let Some(ignores) = filter.enter(root, dir)? {
+        walk(root, dir, filter, &ignores, &mut dirs, &mut file_dirs)?;
+    }
+    Ok((dirs, file_dirs))
+}
+
+// 遍历过程中被删除的文件和目录返回 None, 当作不存在
+fn vanished<T>(rsl: io::Result<T>) -> io::Result<Option<T>> {
+    match rsl {
+        Ok(d) => Ok(Some(d)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn walk(
+    root: &Path,
+    dir: &Path,
+    filter: &Filter,
+    ignores: &Ignores,
+    dirs: &mut Vec<String>,
+    file_dirs: &mut FileDirs,
+) -> CommonResult<()> {
+    let path = dir.to_str().unwrap().to_string();
+    let entries = match vanished(std::fs::read_dir(dir))? {
+        Some(d) => d,
+        None => return Ok(()),
+    };
+    for entry in entries {
         let each_path = entry?;
-        let x_meta = each_path.metadata()?;
+        let x_meta = match vanished(each_path.metadata())? {
+            Some(d) => d,
+            None => continue,
+        };
+        if filter.skip(ignores, root, &each_path.path(), x_meta.is_dir()) {
+            continue;
+        }
         if x_meta.is_dir() {
-            let (child_dirs, child_file_dirs) =
-                walk_all_dir(&each_path.path().to_str().unwrap().to_owned())?;
-            for path in child_dirs {
-                dirs.push(path);
-            }
-            for f_d in child_file_dirs {
-                match file_dirs.get_mut(&f_d.0) {
-                    None => {
-                        let mut set = HashSet::new();
-                        set.extend(f_d.1);
-                        file_dirs.insert(f_d.0, set);
-                    }
-                    Some(d) => {
-                        d.extend(f_d.1);
-                    }
-                };
-            }
-            dirs.push(each_path.path().to_str().unwrap().to_string());
+            let child = each_path.path();
+            walk(
+                root,
+                &child,
+                filter,
+                &filter.enter_dir(ignores, &child)?,
+                dirs,
+                file_dirs,
+            )?;
         } else {
             let file_name = each_path.file_name().to_str().unwrap().to_string();
-            match file_dirs.get_mut(&file_name) {
-                None => {
-                    let mut set = HashSet::new();
-                    set.insert(path.clone());
-                    file_dirs.insert(file_name, set);
-                }
-                Some(d) => {
-                    d.insert(path.clone());
-                }
-            };
+            file_dirs.entry(file_name).or_default().insert(path.clone());
         }
     }
-    dirs.push(path.clone());
-    Ok((dirs, file_dirs))
+    dirs.push(path);
+    Ok(())
 }
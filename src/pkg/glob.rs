@@ -0,0 +1,114 @@
+use crate::pkg::dir::walk_all_dir;
+use crate::pkg::result::CommonResult;
+use regex::Regex;
+use std::path::{Component, Path, PathBuf};
+
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+// 去掉路径中的 ./
+pub fn normalize(path: &Path) -> String {
+    path.components()
+        .filter(|c| *c != Component::CurDir)
+        .collect::<PathBuf>()
+        .to_string_lossy()
+        .to_string()
+}
+
+// * 不跨目录, ** 匹配任意层目录
+pub fn glob_to_regex(pattern: &str) -> CommonResult<Regex> {
+    let mut rsl = "^".to_string();
+    let chars = pattern.chars().collect::<Vec<char>>();
+    let mut index = 0;
+    while index < chars.len() {
+        match chars[index] {
+            '*' if chars.get(index + 1) == Some(&'*') => {
+                if chars.get(index + 2) == Some(&'/') {
+                    rsl.push_str("(.*/)?");
+                    index += 1;
+                } else {
+                    rsl.push_str(".*");
+                }
+                index += 1;
+            }
+            '*' => rsl.push_str("[^/]*"),
+            '?' => rsl.push_str("[^/]"),
+            '[' => match chars[index..].iter().position(|c| *c == ']') {
+                Some(end) => {
+                    let class = chars[index + 1..index + end].iter().collect::<String>();
+                    let class = match class.strip_prefix('!') {
+                        Some(c) => format!("^{}", c),
+                        None => class,
+                    };
+                    rsl.push_str(&format!("[{}]", class.replace('\\', "\\\\")));
+                    index += end;
+                }
+                None => rsl.push_str("\\["),
+            },
+            c => rsl.push_str(&regex::escape(&c.to_string())),
+        }
+        index += 1;
+    }
+    rsl.push('$');
+    Ok(Regex::new(&rsl)?)
+}
+
+// 展开 glob, 结果按路径排序
+pub fn glob(pattern: &str) -> CommonResult<Vec<String>> {
+    let pattern = normalize(Path::new(pattern));
+    let mut base = PathBuf::new();
+    for c in Path::new(&pattern).components() {
+        if is_glob(&c.as_os_str().to_string_lossy()) {
+            break;
+        }
+        base.push(c);
+    }
+    if base.as_os_str().is_empty() {
+        base.push(".");
+    }
+    if !base.is_dir() {
+        return Ok(vec![]);
+    }
+    let re = glob_to_regex(&pattern)?;
+    let (_, file_dirs) = walk_all_dir(&base.to_string_lossy().to_string())?;
+    let mut rsl = file_dirs
+        .iter()
+        .flat_map(|(name, dirs)| {
+            dirs.iter()
+                .map(move |d| normalize(&Path::new(d).join(name)))
+        })
+        .filter(|p| re.is_match(p))
+        .collect::<Vec<String>>();
+    rsl.sort();
+    Ok(rsl)
+}
+
+#[test]
+fn test_glob() {
+    let re = glob_to_regex("src/**/*.vue").unwrap();
+    assert!(re.is_match("src/a.vue"));
+    assert!(re.is_match("src/views/home/a.vue"));
+    assert!(!re.is_match("src/a.vue.bak"));
+    let re = glob_to_regex("a/[!b]?.reg").unwrap();
+    assert!(re.is_match("a/cd.reg"));
+    assert!(!re.is_match("a/bd.reg"));
+    assert_eq!(
+        glob("./res/test/reg/rules/*.reg").unwrap(),
+        vec![
+            "res/test/reg/rules/color.reg",
+            "res/test/reg/rules/size.reg"
+        ]
+    );
+    assert!(glob("./res/test/none/*.reg").unwrap().is_empty());
+    // @import 的文件不受 .gitignore 影响
+    let dir = std::env::temp_dir().join("fcss_test_glob");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("gen")).unwrap();
+    std::fs::write(dir.join(".gitignore"), "gen/\n*.reg\n").unwrap();
+    std::fs::write(dir.join("a.reg"), "").unwrap();
+    std::fs::write(dir.join("gen").join("b.reg"), "").unwrap();
+    let pattern = format!("{}/**/*.reg", dir.to_string_lossy());
+    assert_eq!(glob(&pattern).unwrap().len(), 2);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
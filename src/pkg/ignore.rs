@@ -0,0 +1,247 @@
+use crate::pkg::glob::glob_to_regex;
+use crate::pkg::result::CommonResult;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+// 一条 gitignore 风格的规则
+#[derive(Debug, Clone)]
+struct Rule {
+    re: Regex,
+    negate: bool,
+    dir_only: bool,
+}
+
+impl Rule {
+    fn parse(line: &str) -> CommonResult<Option<Rule>> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+        let (negate, line) = match line.strip_prefix('!') {
+            Some(l) => (true, l),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(l) => (true, l),
+            None => (false, line),
+        };
+        // 不含 / 的规则匹配任意层级, 否则相对于规则所在的目录
+        let pattern = match line.strip_prefix('/') {
+            Some(l) => l.to_string(),
+            None if line.contains('/') => line.to_string(),
+            None => format!("**/{}", line),
+        };
+        Ok(Some(Rule {
+            re: glob_to_regex(&pattern)?,
+            negate,
+            dir_only,
+        }))
+    }
+}
+
+// 一组规则和它们生效的目录
+#[derive(Debug, Clone)]
+struct Ignore {
+    base: PathBuf,
+    rules: Vec<Rule>,
+}
+
+fn parse_rules(lines: &[String]) -> CommonResult<Vec<Rule>> {
+    let mut rules = vec![];
+    for line in lines {
+        rules.extend(Rule::parse(line)?);
+    }
+    Ok(rules)
+}
+
+impl Ignore {
+    fn load(dir: &Path) -> CommonResult<Option<Self>> {
+        let file = dir.join(".gitignore");
+        if !file.is_file() {
+            return Ok(None);
+        }
+        let lines = fs::read_to_string(file)?
+            .lines()
+            .map(String::from)
+            .collect::<Vec<String>>();
+        Ok(Some(Ignore {
+            base: dir.to_path_buf(),
+            rules: parse_rules(&lines)?,
+        }))
+    }
+    // 后面的规则优先, 没有规则匹配时返回 None
+    fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.base).ok()?.to_string_lossy();
+        self.rules
+            .iter()
+            .rev()
+            .find(|r| (is_dir || !r.dir_only) && r.re.is_match(&relative))
+            .map(|r| !r.negate)
+    }
+}
+
+// 目录 => .gitignore 的修改时间和解析后的规则, 修改时间变化时重新读取
+type GitIgnores = HashMap<PathBuf, (Option<SystemTime>, Option<Ignore>)>;
+
+// 监听和遍历目录时的过滤条件, 路径都相对于监听的根目录
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    include: Vec<Rule>,
+    exclude: Vec<Rule>,
+    // clone 出来的 Filter 共用
+    git_ignores: Arc<Mutex<GitIgnores>>,
+    // 为 true 时不读取 .gitignore
+    no_git_ignore: bool,
+}
+
+// 遍历过程中从根目录到当前目录所有生效的规则
+pub struct Ignores(Vec<Ignore>);
+
+impl Filter {
+    pub fn new(include: &[String], exclude: &[String]) -> CommonResult<Self> {
+        Ok(Filter {
+            include: parse_rules(include)?,
+            exclude: parse_rules(exclude)?,
+            git_ignores: Default::default(),
+            no_git_ignore: false,
+        })
+    }
+    // 不过滤任何文件
+    pub fn none() -> Self {
+        Filter {
+            no_git_ignore: true,
+            ..Default::default()
+        }
+    }
+    fn git_ignore(&self, dir: &Path) -> CommonResult<Option<Ignore>> {
+        if self.no_git_ignore {
+            return Ok(None);
+        }
+        let modified = fs::metadata(dir.join(".gitignore"))
+            .and_then(|m| m.modified())
+            .ok();
+        let mut cache = self.git_ignores.lock().unwrap();
+        if let Some((m, ignore)) = cache.get(dir) {
+            if *m == modified {
+                return Ok(ignore.clone());
+            }
+        }
+        let ignore = Ignore::load(dir)?;
+        cache.insert(dir.to_path_buf(), (modified, ignore.clone()));
+        Ok(ignore)
+    }
+    // 根目录的规则, 包括配置的 exclude 和根目录下的 .gitignore
+    pub fn root(&self, root: &Path) -> CommonResult<Ignores> {
+        let mut ignores = vec![Ignore {
+            base: root.to_path_buf(),
+            rules: self.exclude.clone(),
+        }];
+        ignores.extend(self.git_ignore(root)?);
+        Ok(Ignores(ignores))
+    }
+    // 进入子目录, 加上子目录下的 .gitignore
+    pub fn enter_dir(&self, ignores: &Ignores, dir: &Path) -> CommonResult<Ignores> {
+        let mut rsl = ignores.0.clone();
+        rsl.extend(self.git_ignore(dir)?);
+        Ok(Ignores(rsl))
+    }
+    pub fn skip(&self, ignores: &Ignores, root: &Path, path: &Path, is_dir: bool) -> bool {
+        let ignored = ignores
+            .0
+            .iter()
+            .rev()
+            .find_map(|i| i.matched(path, is_dir))
+            .unwrap_or(false);
+        if ignored || is_dir || self.include.is_empty() {
+            return ignored;
+        }
+        let relative = match path.strip_prefix(root) {
+            Ok(r) => r.to_string_lossy().to_string(),
+            Err(_) => return false,
+        };
+        !self.include.iter().any(|r| r.re.is_match(&relative))
+    }
+    // 从根目录逐层进入 dir, dir 或沿途的目录被忽略时返回 None
+    pub fn enter(&self, root: &Path, dir: &Path) -> CommonResult<Option<Ignores>> {
+        let relative = dir
+            .strip_prefix(root)
+            .map_err(|_| format!("{} is not under {}", dir.display(), root.display()))?;
+        let mut ignores = self.root(root)?;
+        let mut current = root.to_path_buf();
+        for c in relative.components() {
+            current.push(c);
+            if self.skip(&ignores, root, &current, true) {
+                return Ok(None);
+            }
+            ignores = self.enter_dir(&ignores, &current)?;
+        }
+        Ok(Some(ignores))
+    }
+    // 检查根目录下的任意路径, 沿途的目录被忽略时也算忽略
+    pub fn is_ignored(&self, root: &Path, path: &Path) -> bool {
+        let parent = match path.parent() {
+            Some(p) if path.starts_with(root) && path != root => p,
+            _ => return false,
+        };
+        match self.enter(root, parent) {
+            Ok(Some(ignores)) => self.skip(&ignores, root, path, path.is_dir()),
+            Ok(None) => true,
+            Err(_) => false,
+        }
+    }
+}
+
+// path 所在的监听根目录, 有嵌套时取最深的
+pub fn root_of<'a>(roots: &'a [PathBuf], path: &Path) -> Option<&'a Path> {
+    roots
+        .iter()
+        .filter(|r| path.starts_with(r))
+        .max_by_key(|r| r.as_os_str().len())
+        .map(|r| r.as_path())
+}
+
+#[test]
+fn test_filter() {
+    let root = std::env::temp_dir().join("fcss_test_filter");
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("src/gen")).unwrap();
+    fs::create_dir_all(root.join("node_modules/x")).unwrap();
+    fs::write(root.join(".gitignore"), "# build\n/dist\n*.bak.vue\n").unwrap();
+    fs::write(root.join("src/.gitignore"), "gen/\n!keep.bak.vue\n").unwrap();
+    let filter = Filter::new(
+        &["src/**/*.vue".to_string()],
+        &["node_modules/".to_string()],
+    )
+    .unwrap();
+    let ignored = |p: &str| filter.is_ignored(&root, &root.join(p));
+    assert!(ignored("node_modules/x/a.vue"));
+    assert!(ignored("dist/a.vue"));
+    assert!(ignored("src/a.bak.vue"));
+    assert!(!ignored("src/keep.bak.vue"));
+    assert!(ignored("src/gen/a.vue"));
+    assert!(!ignored("src/views/a.vue"));
+    // 不在 include 中
+    assert!(ignored("a.vue"));
+    assert!(!ignored("src"));
+    // .gitignore 修改后重新读取
+    fs::write(root.join("src/.gitignore"), "views/\n").unwrap();
+    fs::File::options()
+        .write(true)
+        .open(root.join("src/.gitignore"))
+        .unwrap()
+        .set_modified(SystemTime::now() + std::time::Duration::from_secs(1))
+        .unwrap();
+    assert!(!ignored("src/gen/a.vue"));
+    assert!(ignored("src/views/a.vue"));
+    // 嵌套的根目录取最深的
+    let roots = vec![root.clone(), root.join("src")];
+    assert_eq!(
+        root_of(&roots, &root.join("src/a.vue")),
+        Some(roots[1].as_path())
+    );
+    fs::remove_dir_all(&root).unwrap();
+}
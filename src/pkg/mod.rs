@@ -1,2 +1,4 @@
 pub mod dir;
+pub mod glob;
+pub mod ignore;
 pub mod result;
@@ -1,5 +1,4 @@
-use std::error::Error;
 use core::result;
+use std::error::Error;
 
-pub type CommonResult<T> = result::Result<T,Box<dyn Error>>;
-
+pub type CommonResult<T> = result::Result<T, Box<dyn Error>>;
@@ -0,0 +1,312 @@
+use crate::config::error::position;
+use crate::config::reg::Signatures;
+use crate::pkg::dir::walk_dir;
+use crate::pkg::ignore::Filter;
+use crate::pkg::result::CommonResult;
+use crate::replace::bundle::Bundle;
+use crate::replace::extractor::{build_file, generate, Extractor, Extractors};
+use crate::replace::printer::Style;
+use crate::replace::resolve::resolve_class;
+use serde_derive::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub files: usize,
+    pub written: Vec<String>,
+    // 文件 => 没有匹配到规则的类名
+    pub unresolved: Vec<(String, Vec<String>)>,
+    pub errors: Vec<(String, String)>,
+}
+
+impl Report {
+    pub fn ok(&self) -> bool {
+        self.unresolved.is_empty() && self.errors.is_empty()
+    }
+}
+
+// dirs 下所有有 extractor 处理的文件, 按路径排序
+pub fn source_files(
+    dirs: &[String],
+    filter: &Filter,
+    extractors: &Extractors,
+) -> CommonResult<Vec<PathBuf>> {
+    let mut rsl = vec![];
+    for dir in dirs {
+        let root = fs::canonicalize(dir)?;
+        let (_, file_dirs) = walk_dir(&root, &root, filter)?;
+        for (name, dirs) in file_dirs {
+            for d in dirs {
+                let path = Path::new(&d).join(&name);
+                if extractors.get(&path).is_some() {
+                    rsl.push(path);
+                }
+            }
+        }
+    }
+    rsl.sort();
+    rsl.dedup();
+    Ok(rsl)
+}
+
+// 生成 css 但不写回文件
+fn generate_file(
+    path: &Path,
+    extractor: &dyn Extractor,
+    signatures: &Signatures,
+) -> CommonResult<(bool, Vec<String>)> {
+    let content = fs::read_to_string(path)?;
+    Ok((
+        false,
+        generate(&content, extractor, signatures, Style::Plain)?.1,
+    ))
+}
+
+// 处理所有文件, 单个文件出错不影响其他文件, 有 bundle 时最后写入合并的样式文件
+pub fn build(
+    dirs: &[String],
+    filter: &Filter,
+    extractors: &Extractors,
+    signatures: &Signatures,
+    style: Style,
+    inject: bool,
+    mut bundle: Option<&mut Bundle>,
+) -> CommonResult<Report> {
+    let mut report = Report::default();
+    for path in source_files(dirs, filter, extractors)? {
+        let extractor = match extractors.get(&path) {
+            Some(e) => e,
+            None => continue,
+        };
+        let p = path.to_string_lossy().to_string();
+        report.files += 1;
+        if let Some(b) = bundle.as_mut() {
+            if let Err(e) = b.update(&path, extractor, signatures) {
+                report.errors.push((p, e.to_string()));
+                continue;
+            }
+        }
+        let built = if inject {
+            build_file(&p, extractor, signatures, style)
+        } else {
+            generate_file(&path, extractor, signatures)
+        };
+        match built {
+            Ok((written, unresolved)) => {
+                if written {
+                    report.written.push(p.clone());
+                }
+                if !unresolved.is_empty() {
+                    report.unresolved.push((p, unresolved));
+                }
+            }
+            Err(e) => report.errors.push((p, e.to_string())),
+        }
+    }
+    if let Some(b) = bundle {
+        let p = b.path().to_string_lossy().to_string();
+        match b.write() {
+            Ok((true, _)) => report.written.push(p),
+            Ok(_) => {}
+            Err(e) => report.errors.push((p, e.to_string())),
+        }
+    }
+    Ok(report)
+}
+
+// 签名没有对应规则的类名
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Unknown {
+    pub line: usize,
+    pub column: usize,
+    pub class: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct CheckReport {
+    pub files: usize,
+    pub unknown: Vec<(String, Vec<Unknown>)>,
+    pub errors: Vec<(String, String)>,
+}
+
+impl CheckReport {
+    pub fn ok(&self) -> bool {
+        self.unknown.is_empty() && self.errors.is_empty()
+    }
+}
+
+// 返回未知的类和展开规则出错的类, 和 build 一样展开, 签名相同但没有匹配的规则也算未知
+pub fn check_content(
+    content: &str,
+    extractor: &dyn Extractor,
+    signatures: &Signatures,
+) -> CommonResult<(Vec<Unknown>, Vec<String>)> {
+    let mut rsl = vec![];
+    let mut errors = vec![];
+    for c in extractor.classes(content)? {
+        let (line, column) = position(content, c.offset);
+        match resolve_class(&c.name, signatures) {
+            Ok(Some(_)) => {}
+            Ok(None) => rsl.push(Unknown {
+                line,
+                column,
+                class: c.name,
+            }),
+            Err(e) => errors.push(format!("{}:{} {}: {}", line, column, c.name, e)),
+        }
+    }
+    rsl.sort_by_key(|u| (u.line, u.column));
+    Ok((rsl, errors))
+}
+
+// 只读取不写入
+pub fn check(
+    dirs: &[String],
+    filter: &Filter,
+    extractors: &Extractors,
+    signatures: &Signatures,
+) -> CommonResult<CheckReport> {
+    let mut report = CheckReport::default();
+    for path in source_files(dirs, filter, extractors)? {
+        let extractor = match extractors.get(&path) {
+            Some(e) => e,
+            None => continue,
+        };
+        let p = path.to_string_lossy().to_string();
+        report.files += 1;
+        let checked = fs::read_to_string(&path)
+            .map_err(|e| e.into())
+            .and_then(|content| check_content(&content, extractor, signatures));
+        match checked {
+            Ok((unknown, errors)) => {
+                for e in errors {
+                    report.errors.push((p.clone(), e));
+                }
+                if !unknown.is_empty() {
+                    report.unknown.push((p, unknown));
+                }
+            }
+            Err(e) => report.errors.push((p, e.to_string())),
+        }
+    }
+    Ok(report)
+}
+
+#[test]
+fn test_build() {
+    use crate::config::reg::parse;
+    use std::collections::HashMap;
+    let dir = std::env::temp_dir().join("fcss_test_build");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("node_modules")).unwrap();
+    fs::write(
+        dir.join("a.vue"),
+        "<template><p class=\"h-1\"></p></template>",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("b.vue"),
+        "<template><p class=\"h-2 x\"></p></template>",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("c.vue"),
+        "<template><p class=\"bad-1\"></p></template>",
+    )
+    .unwrap();
+    fs::write(dir.join("d.js"), "").unwrap();
+    fs::write(dir.join("node_modules/e.vue"), "<template></template>").unwrap();
+    let (_, css) = parse(".h-$1{\nheight:$1px;\n}\n.bad-$1{\nwidth:$2px;\n}")
+        .ok()
+        .unwrap();
+    let signatures = css.get_signature().unwrap();
+    let mut config = HashMap::new();
+    config.insert("vue".to_string(), "vue".to_string());
+    let extractors = Extractors::new(&config).unwrap();
+    let filter = Filter::new(&[], &["node_modules/".to_string()]).unwrap();
+    let dirs = vec![dir.to_str().unwrap().to_string()];
+    let report = build(
+        &dirs,
+        &filter,
+        &extractors,
+        &signatures,
+        Style::Plain,
+        true,
+        None,
+    )
+    .unwrap();
+    let dir = fs::canonicalize(&dir).unwrap();
+    let p = |n: &str| dir.join(n).to_str().unwrap().to_string();
+    assert_eq!(report.files, 3);
+    assert_eq!(report.written, vec![p("a.vue"), p("b.vue")]);
+    assert_eq!(report.unresolved, vec![(p("b.vue"), vec!["x".to_string()])]);
+    assert_eq!(report.errors.len(), 1);
+    assert_eq!(report.errors[0].0, p("c.vue"));
+    assert!(!report.ok());
+    assert!(fs::read_to_string(dir.join("a.vue"))
+        .unwrap()
+        .contains(".h-1{\nheight:1px;\n}"));
+    // 只生成合并的样式文件, 不写回 b.vue
+    fs::write(
+        dir.join("b.vue"),
+        "<template><p class=\"h-3\"></p></template>",
+    )
+    .unwrap();
+    let mut bundle = Bundle::new(dir.join("fcss.css").to_str().unwrap(), Style::Plain, false);
+    let report = build(
+        &dirs,
+        &filter,
+        &extractors,
+        &signatures,
+        Style::Plain,
+        false,
+        Some(&mut bundle),
+    )
+    .unwrap();
+    assert_eq!(report.written, vec![p("fcss.css")]);
+    assert_eq!(
+        fs::read_to_string(dir.join("fcss.css")).unwrap(),
+        ".h-1{\nheight:1px;\n}\n.h-3{\nheight:3px;\n}\n"
+    );
+    assert!(!fs::read_to_string(dir.join("b.vue"))
+        .unwrap()
+        .contains("<style>"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_check() {
+    use crate::config::reg::parse;
+    use crate::replace::vue::Vue;
+    // b-1-fff 的签名有规则但是字面段不匹配, hw-12-21 展开时 $3 没有绑定
+    let (_, css) = parse(
+        ".h-$1{\nheight:$1px;\n}\n.p-$1{\npadding:$1px;\n}\n\
+         .b-$1-000{\nborder:$1px solid #000;\n}\n.hw-$1-$2{\nheight:$3px;\n}",
+    )
+    .ok()
+    .unwrap();
+    let signatures = css.get_signature().unwrap();
+    assert!(signatures.contains_key(".b-$1-$2"));
+    let content = fs::read_to_string("./res/test/vue/demo.vue").unwrap();
+    let (unknown, errors) = check_content(&content, &Vue, &signatures).unwrap();
+    let at = |line, column, class: &str| Unknown {
+        line,
+        column,
+        class: class.to_string(),
+    };
+    assert_eq!(
+        unknown,
+        vec![
+            at(2, 20, "w-12"),
+            at(2, 37, "w-24"),
+            at(5, 20, "b-1-fff"),
+            at(6, 20, "tcp"),
+            at(10, 40, "c-fff"),
+            at(10, 50, "c-000"),
+        ]
+    );
+    // 展开规则出错时报告出错的规则, 而不是未知的类
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].starts_with("9:17 hw-12-21: $3 is not bound"));
+}
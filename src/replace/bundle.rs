@@ -0,0 +1,177 @@
+use crate::config::reg::Signatures;
+use crate::pkg::result::CommonResult;
+use crate::replace::extractor::Extractor;
+use crate::replace::printer::{print_mapped, Style};
+use crate::replace::resolve::{resolve_class, Rule};
+use crate::replace::sourcemap::source_map;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// 所有文件用到的类合并生成一个样式文件
+pub struct Bundle {
+    path: PathBuf,
+    style: Style,
+    // 是否在同目录生成 .map 文件
+    source_map: bool,
+    // 文件 => 用到的类名
+    files: HashMap<PathBuf, BTreeSet<String>>,
+    // 类名 => 展开后的规则, None 表示没有匹配的规则
+    cache: HashMap<String, Option<Vec<Rule>>>,
+}
+
+impl Bundle {
+    pub fn new(path: &str, style: Style, source_map: bool) -> Self {
+        Bundle {
+            path: PathBuf::from(path),
+            style,
+            source_map,
+            files: Default::default(),
+            cache: Default::default(),
+        }
+    }
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    pub fn map_path(&self) -> PathBuf {
+        PathBuf::from(format!("{}.map", self.path.to_string_lossy()))
+    }
+    // 重新读取一个文件的类名, 其他文件不受影响, 有类名生成出错时保留这个文件之前的类名
+    pub fn update(
+        &mut self,
+        file: &Path,
+        extractor: &dyn Extractor,
+        signatures: &Signatures,
+    ) -> CommonResult<()> {
+        let content = fs::read_to_string(file)?;
+        let classes = extractor
+            .classes(&content)?
+            .into_iter()
+            .map(|c| c.name)
+            .collect::<BTreeSet<String>>();
+        for class in &classes {
+            if !self.cache.contains_key(class) {
+                let rules =
+                    resolve_class(class, signatures).map_err(|e| format!("{}: {}", class, e))?;
+                self.cache.insert(class.clone(), rules);
+            }
+        }
+        self.files.insert(file.to_path_buf(), classes);
+        Ok(())
+    }
+    pub fn remove(&mut self, file: &Path) -> bool {
+        self.files.remove(file).is_some()
+    }
+    // 按类名排序去重, 和文件的处理顺序无关, 返回 css, source map 和没有匹配到规则的类名
+    pub fn generate(&self) -> (String, Option<String>, Vec<String>) {
+        let classes = self.files.values().flatten().collect::<BTreeSet<&String>>();
+        let mut rules = vec![];
+        let mut unresolved = vec![];
+        for class in classes {
+            match &self.cache[class] {
+                Some(r) => rules.extend(r.iter().cloned()),
+                None => unresolved.push(class.clone()),
+            }
+        }
+        let (mut css, mappings) = print_mapped(&rules, self.style);
+        if !self.source_map {
+            return (css, None, unresolved);
+        }
+        let map_path = self.map_path();
+        let map = source_map(&css, &mappings, &self.path, &map_path);
+        let name = map_path.file_name().unwrap_or_default().to_string_lossy();
+        css.push_str(&format!("/*# sourceMappingURL={} */\n", name));
+        (css, Some(map), unresolved)
+    }
+    // 内容没有变化时不写入, 返回是否有写入和没有匹配到规则的类名
+    pub fn write(&self) -> CommonResult<(bool, Vec<String>)> {
+        let (css, map, unresolved) = self.generate();
+        let mut written = write_changed(&self.path, &css)?;
+        if let Some(map) = map {
+            written |= write_changed(&self.map_path(), &map)?;
+        }
+        Ok((written, unresolved))
+    }
+}
+
+fn write_changed(path: &Path, content: &str) -> CommonResult<bool> {
+    if fs::read_to_string(path).ok().as_deref() == Some(content) {
+        return Ok(false);
+    }
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, content)?;
+    Ok(true)
+}
+
+#[test]
+fn test_bundle() {
+    use crate::config::reg::parse;
+    use crate::replace::jsx::Jsx;
+    use crate::replace::vue::Vue;
+    let (_, css) = parse(".h-$1{\nheight:$1px;\n}\n.w-$1{\nwidth:$1px;\n}")
+        .ok()
+        .unwrap();
+    let signatures = css.get_signature().unwrap();
+    let dir = std::env::temp_dir().join("fcss_test_bundle");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let a = dir.join("a.vue");
+    let b = dir.join("b.tsx");
+    fs::write(&a, "<template><p class=\"w-2 h-1 x\"></p></template>").unwrap();
+    fs::write(&b, "<p className=\"h-1 w-1\"/>").unwrap();
+    let mut bundle = Bundle::new(
+        dir.join("out/fcss.css").to_str().unwrap(),
+        Style::Plain,
+        false,
+    );
+    bundle.update(&a, &Vue, &signatures).unwrap();
+    bundle.update(&b, &Jsx, &signatures).unwrap();
+    assert_eq!(bundle.write().unwrap(), (true, vec!["x".to_string()]));
+    assert_eq!(
+        fs::read_to_string(bundle.path()).unwrap(),
+        ".h-1{\nheight:1px;\n}\n.w-1{\nwidth:1px;\n}\n.w-2{\nwidth:2px;\n}\n"
+    );
+    // 内容没有变化不再写入
+    bundle.update(&b, &Jsx, &signatures).unwrap();
+    assert!(!bundle.write().unwrap().0);
+    assert!(bundle.remove(&a));
+    assert!(bundle.write().unwrap().0);
+    assert_eq!(
+        fs::read_to_string(bundle.path()).unwrap(),
+        ".h-1{\nheight:1px;\n}\n.w-1{\nwidth:1px;\n}\n"
+    );
+    // source map 的路径相对于 map 文件
+    fs::write(dir.join("rules.reg"), ".w-$1{\nwidth:$1px;\n}").unwrap();
+    let css = crate::config::reg::load(
+        "@import(./rules.reg);",
+        dir.join("fcss.json").to_str().unwrap(),
+        &[],
+    )
+    .unwrap();
+    let signatures = css.get_signature().unwrap();
+    let mut b = Bundle::new(
+        dir.join("out/map.css").to_str().unwrap(),
+        Style::Plain,
+        true,
+    );
+    b.update(&a, &Vue, &signatures).unwrap();
+    assert!(b.write().unwrap().0);
+    assert_eq!(
+        fs::read_to_string(dir.join("out/map.css")).unwrap(),
+        ".w-2{\nwidth:2px;\n}\n/*# sourceMappingURL=map.css.map */\n"
+    );
+    let map = fs::read_to_string(dir.join("out/map.css.map")).unwrap();
+    assert!(map.contains("\"sources\":[\"../rules.reg\"]"));
+    assert!(map.contains("\"mappings\":\"AAAA\""));
+    assert!(!b.write().unwrap().0);
+    // 出错的文件不影响其他文件
+    fs::write(&a, "<template><p class=\"m-1\"></p></template>").unwrap();
+    let (_, css) = parse(".m-$1{\nmargin:$2px;\n}").ok().unwrap();
+    assert!(bundle
+        .update(&a, &Vue, &css.get_signature().unwrap())
+        .is_err());
+    assert!(!bundle.write().unwrap().0);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,245 @@
+use crate::replace::signature::{arbitrary, segments};
+
+// 顶层(不在括号和字符串里)的字符位置
+fn top_level(s: &str) -> Vec<(usize, char)> {
+    let mut rsl = vec![];
+    let mut depth = 0;
+    let mut quote = None;
+    let mut chars = s.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        if let Some(q) = quote {
+            if c == '\\' {
+                chars.next();
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '\'' | '"' | '`' => quote = Some(c),
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            _ if depth == 0 => rsl.push((idx, c)),
+            _ => {}
+        }
+    }
+    rsl
+}
+// s 以括号开头, 返回与之配对的右括号位置
+pub fn matching_close(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut chars = s.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        if let Some(q) = quote {
+            if c == '\\' {
+                chars.next();
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '\'' | '"' | '`' => quote = Some(c),
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(idx);
+                }
+            }
+            _ => {}
+        }
+        if depth == 0 {
+            return None;
+        }
+    }
+    None
+}
+// 表达式中的一段和它的位置
+type Part<'a> = (usize, &'a str);
+
+fn split_top<'a>(s: &'a str, sep: &str) -> Vec<Part<'a>> {
+    let mut rsl = vec![];
+    let mut start = 0;
+    for (idx, _) in top_level(s) {
+        if idx >= start && s[idx..].starts_with(sep) {
+            rsl.push((start, &s[start..idx]));
+            start = idx + sep.len();
+        }
+    }
+    rsl.push((start, &s[start..]));
+    rsl
+}
+// cond ? a : b, 返回 a 和 b 以及它们的位置
+fn split_ternary(s: &str) -> Option<(Part<'_>, Part<'_>)> {
+    let mut question = None;
+    let mut nested = 0;
+    for (idx, c) in top_level(s) {
+        let rest = &s[idx..];
+        match (c, question) {
+            ('?', None) if !rest.starts_with("?.") && !rest.starts_with("??") => {
+                question = Some(idx)
+            }
+            ('?', Some(_)) if !rest.starts_with("?.") && !rest.starts_with("??") => nested += 1,
+            (':', Some(q)) => {
+                if nested == 0 {
+                    return Some(((q + 1, &s[q + 1..idx]), (idx + 1, &s[idx + 1..])));
+                }
+                nested -= 1;
+            }
+            _ => {}
+        }
+    }
+    None
+}
+pub fn string_literal(s: &str) -> Option<&str> {
+    let first = s.chars().next()?;
+    if s.len() < 2 || !"'\"`".contains(first) || !s.ends_with(first) {
+        return None;
+    }
+    let inner = &s[1..s.len() - 1];
+    if first == '`' && inner.contains("${") {
+        return None;
+    }
+    Some(inner)
+}
+// [] 中的任意值和规则匹配时一样检查, 其他部分不能有表达式中的符号
+fn is_class_like(s: &str) -> bool {
+    match s.chars().next() {
+        Some(c) if c.is_alphabetic() || c == '-' || c == '_' => {
+            segments(s).into_iter().all(|segment| {
+                if segment.starts_with('[') {
+                    arbitrary(segment).is_some()
+                } else {
+                    segment.chars().all(|c| !"'\"`{}()[]<>=,;".contains(c))
+                }
+            })
+        }
+        _ => false,
+    }
+}
+// 按空白分开, 同时返回每一段的位置
+pub fn words(s: &str) -> Vec<(usize, String)> {
+    let mut rsl = vec![];
+    let mut start = None;
+    for (idx, c) in s.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(st)) => {
+                rsl.push((st, s[st..idx].to_string()));
+                start = None;
+            }
+            (false, None) => start = Some(idx),
+            _ => {}
+        }
+    }
+    if let Some(st) = start {
+        rsl.push((st, s[st..].to_string()));
+    }
+    rsl
+}
+fn push_class_like(rsl: &mut Vec<(usize, String)>, offset: usize, s: &str) {
+    rsl.extend(
+        words(s)
+            .into_iter()
+            .filter(|(_, c)| is_class_like(c))
+            .map(|(at, c)| (offset + at, c)),
+    );
+}
+// expr 中的类名, 位置加上 expr 的位置
+fn push_nested(rsl: &mut Vec<(usize, String)>, offset: usize, expr: &str) {
+    rsl.extend(
+        bind_class_offsets(expr)
+            .into_iter()
+            .map(|(at, c)| (offset + at, c)),
+    );
+}
+
+// 从 :class 绑定的表达式中取出类名和它在表达式中的位置: 对象的key, 数组元素, 三元表达式的分支
+pub fn bind_class_offsets(expr: &str) -> Vec<(usize, String)> {
+    let mut rsl = vec![];
+    let start = expr.len() - expr.trim_start().len();
+    let expr = expr.trim();
+    if expr.starts_with('(') && expr.ends_with(')') && top_level(expr).is_empty() {
+        push_nested(&mut rsl, start + 1, &expr[1..expr.len() - 1]);
+        return rsl;
+    }
+    if let Some(((a_at, a), (b_at, b))) = split_ternary(expr) {
+        push_nested(&mut rsl, start + a_at, a);
+        push_nested(&mut rsl, start + b_at, b);
+        return rsl;
+    }
+    let or = split_top(expr, "||");
+    if or.len() > 1 {
+        for (at, e) in or {
+            push_nested(&mut rsl, start + at, e);
+        }
+        return rsl;
+    }
+    let and = split_top(expr, "&&");
+    if let Some((at, e)) = and.last().filter(|_| and.len() > 1) {
+        push_nested(&mut rsl, start + at, e);
+        return rsl;
+    }
+    if let Some(s) = string_literal(expr) {
+        push_class_like(&mut rsl, start + 1, s);
+    } else if expr.starts_with('[') && expr.ends_with(']') {
+        for (at, e) in split_top(&expr[1..expr.len() - 1], ",") {
+            push_nested(&mut rsl, start + 1 + at, e);
+        }
+    } else if expr.starts_with('{') && expr.ends_with('}') {
+        for (at, entry) in split_top(&expr[1..expr.len() - 1], ",") {
+            let raw = split_top(entry, ":")[0].1;
+            let key = raw.trim();
+            if key.starts_with("...") || key.starts_with('[') {
+                continue;
+            }
+            let at = start + 1 + at + raw.len() - raw.trim_start().len();
+            match string_literal(key) {
+                Some(k) => push_class_like(&mut rsl, at + 1, k),
+                None => push_class_like(&mut rsl, at, key),
+            }
+        }
+    }
+    rsl
+}
+
+#[test]
+fn test_bind_class_offsets() {
+    let bind_class_literals = |expr| {
+        bind_class_offsets(expr)
+            .into_iter()
+            .map(|(_, c)| c)
+            .collect::<Vec<String>>()
+    };
+    assert_eq!(
+        bind_class_literals("{ 'w-12': wide, \"h-4\": tall, active }"),
+        vec!["w-12", "h-4", "active"]
+    );
+    assert_eq!(
+        bind_class_literals("['p-2', active ? 'c-fff' : 'c-000', { 'm-1': a === 'x' }]"),
+        vec!["p-2", "c-fff", "c-000", "m-1"]
+    );
+    assert_eq!(
+        bind_class_literals("size === 'big' ? (a ? 'w-1' : 'w-2') : `h-${n}`"),
+        vec!["w-1", "w-2"]
+    );
+    assert_eq!(
+        bind_class_literals("ok && 'b-1-fff tcp'"),
+        vec!["b-1-fff", "tcp"]
+    );
+    assert!(bind_class_literals("classNames").is_empty());
+    assert_eq!(
+        bind_class_literals("['w-[calc(100%-2px)]', ok && 'grid-cols-[repeat(2,1fr)]']"),
+        vec!["w-[calc(100%-2px)]", "grid-cols-[repeat(2,1fr)]"]
+    );
+    assert!(bind_class_literals("'c-[</style>] a[0] f(x)'").is_empty());
+    let expr = " ok ? { 'w-12': a, h-4 } : ['p-2 m-1', b && \"c-fff\"]";
+    let rsl = bind_class_offsets(expr);
+    assert_eq!(rsl.len(), 5);
+    for (at, c) in rsl {
+        assert_eq!(&expr[at..at + c.len()], c);
+    }
+    assert_eq!(matching_close("{ a: '}' } rest"), Some(9));
+    assert_eq!(matching_close("{ a"), None);
+}
@@ -0,0 +1,158 @@
+use crate::config::reg::Signatures;
+use crate::pkg::result::CommonResult;
+use crate::replace::html::Html;
+use crate::replace::jsx::Jsx;
+use crate::replace::printer::{print, Style};
+use crate::replace::resolve::resolve_class;
+use crate::replace::svelte::Svelte;
+use crate::replace::vue::Vue;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+// 类名和它在文件中的位置
+#[derive(Debug, PartialEq, Clone)]
+pub struct Class {
+    pub offset: usize,
+    pub name: String,
+}
+
+// names 中的位置相对于 offset
+pub fn located(offset: usize, names: Vec<(usize, String)>) -> Vec<Class> {
+    names
+        .into_iter()
+        .map(|(at, name)| Class {
+            offset: offset + at,
+            name,
+        })
+        .collect()
+}
+
+// 从不同类型的文件中取出类名, 并把生成的 css 写回
+pub trait Extractor {
+    fn classes(&self, content: &str) -> CommonResult<Vec<Class>>;
+    // 不支持内嵌样式的文件返回 None
+    fn inject(&self, content: &str, css: &str) -> Option<String>;
+}
+
+pub fn extractor(name: &str) -> CommonResult<Box<dyn Extractor>> {
+    match name {
+        "vue" => Ok(Box::new(Vue)),
+        "html" => Ok(Box::new(Html)),
+        "jsx" => Ok(Box::new(Jsx)),
+        "svelte" => Ok(Box::new(Svelte)),
+        _ => Err(Box::from(format!("unsupported extractor {}", name))),
+    }
+}
+
+// 文件扩展名 => 处理它的 extractor
+pub struct Extractors(HashMap<String, Box<dyn Extractor>>);
+
+impl Extractors {
+    pub fn new(config: &HashMap<String, String>) -> CommonResult<Self> {
+        let mut rsl = HashMap::new();
+        for (ext, name) in config {
+            rsl.insert(ext.trim_start_matches('.').to_string(), extractor(name)?);
+        }
+        Ok(Extractors(rsl))
+    }
+    pub fn extensions(&self) -> Vec<String> {
+        let mut rsl = self.0.keys().cloned().collect::<Vec<String>>();
+        rsl.sort();
+        rsl
+    }
+    pub fn get(&self, path: &Path) -> Option<&dyn Extractor> {
+        let ext = path.extension()?.to_str()?;
+        self.0.get(ext).map(|e| e.as_ref())
+    }
+}
+
+// 文件用到的类名生成的css, 以及没有匹配到规则的类名
+pub fn generate(
+    content: &str,
+    extractor: &dyn Extractor,
+    signatures: &Signatures,
+    style: Style,
+) -> CommonResult<(String, Vec<String>)> {
+    let classes = extractor.classes(content)?;
+    let mut seen = HashSet::new();
+    let mut rules = vec![];
+    let mut unresolved = vec![];
+    for class in classes.iter().map(|c| c.name.as_str()) {
+        if !seen.insert(class) {
+            continue;
+        }
+        match resolve_class(class, signatures)? {
+            Some(r) => rules.extend(r),
+            None => unresolved.push(class.to_string()),
+        }
+    }
+    Ok((print(&rules, style), unresolved))
+}
+
+// 生成该文件用到的css并写回, 返回是否有写入和没有匹配到规则的类名
+pub fn build_file(
+    path: &str,
+    extractor: &dyn Extractor,
+    signatures: &Signatures,
+    style: Style,
+) -> CommonResult<(bool, Vec<String>)> {
+    let content = fs::read_to_string(path)?;
+    let (css, unresolved) = generate(&content, extractor, signatures, style)?;
+    let new_content = match extractor.inject(&content, &css) {
+        Some(c) if c != content => c,
+        _ => return Ok((false, unresolved)),
+    };
+    fs::write(path, new_content)?;
+    Ok((true, unresolved))
+}
+
+pub fn replace(
+    path: &str,
+    extractor: &dyn Extractor,
+    signatures: &Signatures,
+    style: Style,
+) -> CommonResult<bool> {
+    Ok(build_file(path, extractor, signatures, style)?.0)
+}
+
+#[test]
+fn test_replace() {
+    use crate::config::reg::parse;
+    use crate::replace::markup::{STYLE_END, STYLE_START};
+    let (_, css) = parse(".h-$1{\nheight:12px;\n}\n.p-$1{\npadding:2px;\n}")
+        .ok()
+        .unwrap();
+    let signatures = css.get_signature().unwrap();
+    let path = std::env::temp_dir().join("fcss_test_replace.vue");
+    let path = path.to_str().unwrap();
+    fs::copy("./res/test/vue/demo.vue", path).unwrap();
+    assert!(replace(path, &Vue, &signatures, Style::Plain).unwrap());
+    let content = fs::read_to_string(path).unwrap();
+    assert!(content.contains(&format!(
+        "{}\n.h-12{{\nheight:12px;\n}}\n.p-2{{\npadding:2px;\n}}\n.h-4{{\nheight:12px;\n}}\n.p-4{{\npadding:2px;\n}}\n{}",
+        STYLE_START, STYLE_END
+    )));
+    assert!(content.contains(".style-only {"));
+    // 内容没有变化不再写入
+    assert!(!replace(path, &Vue, &signatures, Style::Plain).unwrap());
+    fs::remove_file(path).unwrap();
+    // jsx 不写入
+    assert_eq!(
+        build_file("./res/test/jsx/demo.tsx", &Jsx, &signatures, Style::Plain).unwrap(),
+        (false, vec!["w-12".to_string(), "w-24".to_string()])
+    );
+}
+
+#[test]
+fn test_extractors() {
+    let mut config = HashMap::new();
+    config.insert("vue".to_string(), "vue".to_string());
+    config.insert(".tsx".to_string(), "jsx".to_string());
+    let extractors = Extractors::new(&config).unwrap();
+    assert_eq!(extractors.extensions(), vec!["tsx", "vue"]);
+    assert!(extractors.get(Path::new("/a/b.tsx")).is_some());
+    assert!(extractors.get(Path::new("/a/b.html")).is_none());
+    config.insert("md".to_string(), "markdown".to_string());
+    assert!(Extractors::new(&config).is_err());
+}
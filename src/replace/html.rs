@@ -0,0 +1,35 @@
+use crate::pkg::result::CommonResult;
+use crate::replace::expr::words;
+use crate::replace::extractor::{located, Class, Extractor};
+use crate::replace::markup::{class_attrs, inject_head, tags};
+
+pub struct Html;
+
+impl Extractor for Html {
+    fn classes(&self, content: &str) -> CommonResult<Vec<Class>> {
+        let mut rsl = vec![];
+        for (offset, value) in tags(content).iter().flat_map(class_attrs) {
+            rsl.extend(located(offset, words(value)));
+        }
+        Ok(rsl)
+    }
+    fn inject(&self, content: &str, css: &str) -> Option<String> {
+        Some(inject_head(content, css))
+    }
+}
+
+#[test]
+fn test_html() {
+    let content = std::fs::read_to_string("./res/test/html/demo.html").unwrap();
+    let classes = Html.classes(&content).unwrap();
+    assert_eq!(
+        classes
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<&str>>(),
+        vec!["h-12", "w-12", "p-2"]
+    );
+    for c in classes {
+        assert_eq!(&content[c.offset..c.offset + c.name.len()], c.name);
+    }
+}
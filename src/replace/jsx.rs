@@ -0,0 +1,52 @@
+use crate::pkg::result::CommonResult;
+use crate::replace::expr::{bind_class_offsets, words};
+use crate::replace::extractor::{located, Class, Extractor};
+use crate::replace::markup::attr_value;
+use regex::Regex;
+
+// jsx/tsx 中 className="..." 和 className={...}, 不支持内嵌样式
+pub struct Jsx;
+
+impl Extractor for Jsx {
+    fn classes(&self, content: &str) -> CommonResult<Vec<Class>> {
+        let re = Regex::new(r"(^|\s)class(Name)?\s*=\s*")?;
+        let mut rsl = vec![];
+        for m in re.find_iter(content) {
+            let value = match attr_value(&content[m.end()..]) {
+                Ok((_, v)) => v,
+                Err(_) => continue,
+            };
+            // 引号和 {} 之后才是值
+            let offset = match content[m.end()..].chars().next() {
+                Some('"') | Some('\'') | Some('{') => m.end() + 1,
+                _ => m.end(),
+            };
+            let cls = match value.strip_prefix('{').and_then(|v| v.strip_suffix('}')) {
+                Some(expr) => bind_class_offsets(expr),
+                None => words(value),
+            };
+            rsl.extend(located(offset, cls));
+        }
+        Ok(rsl)
+    }
+    fn inject(&self, _: &str, _: &str) -> Option<String> {
+        None
+    }
+}
+
+#[test]
+fn test_jsx() {
+    let content = std::fs::read_to_string("./res/test/jsx/demo.tsx").unwrap();
+    let classes = Jsx.classes(&content).unwrap();
+    assert_eq!(
+        classes
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<&str>>(),
+        vec!["h-12", "w-12", "w-24", "w-12", "p-2"]
+    );
+    for c in classes {
+        assert_eq!(&content[c.offset..c.offset + c.name.len()], c.name);
+    }
+    assert_eq!(Jsx.inject(&content, ".a{}"), None);
+}
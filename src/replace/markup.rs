@@ -0,0 +1,295 @@
+use crate::replace::expr::matching_close;
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while, take_while1};
+use nom::character::complete::{char, multispace0};
+use nom::combinator::opt;
+use nom::multi::many0;
+use nom::sequence::{delimited, pair, preceded, terminated};
+use nom::IResult;
+
+pub const STYLE_START: &str = "/* fcss:start */";
+pub const STYLE_END: &str = "/* fcss:end */";
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Tag<'a> {
+    pub name: &'a str,
+    pub attrs: Vec<Attr<'a>>,
+    pub self_close: bool,
+}
+
+// 属性名和值在文档中的位置, 值的位置不包括引号
+#[derive(Debug, PartialEq, Clone)]
+pub struct Attr<'a> {
+    pub name: &'a str,
+    pub value: Option<&'a str>,
+    pub offset: usize,
+    pub value_offset: usize,
+}
+
+impl<'a> Tag<'a> {
+    // open_tag 返回的位置相对于标签开头, 加上标签在文档中的位置
+    pub fn at(mut self, start: usize) -> Self {
+        for a in self.attrs.iter_mut() {
+            a.offset += start;
+            a.value_offset += start;
+        }
+        self
+    }
+}
+
+fn tag_name(i: &str) -> IResult<&str, &str> {
+    take_while1(|c: char| c.is_alphanumeric() || c == '-' || c == '_' || c == '.' || c == ':')(i)
+}
+fn attr_name(i: &str) -> IResult<&str, &str> {
+    take_while1(|c: char| !c.is_whitespace() && !"=>/\"'{".contains(c))(i)
+}
+// svelte 和 jsx 中 {表达式} 形式的值, 包含两边的括号
+fn braced(i: &str) -> IResult<&str, &str> {
+    match matching_close(i).filter(|_| i.starts_with('{')) {
+        Some(end) => Ok((&i[end + 1..], &i[..end + 1])),
+        None => Err(nom::Err::Error(nom::error::Error::new(
+            i,
+            nom::error::ErrorKind::Char,
+        ))),
+    }
+}
+pub fn attr_value(i: &str) -> IResult<&str, &str> {
+    alt((
+        delimited(char('"'), take_while(|c| c != '"'), char('"')),
+        delimited(char('\''), take_while(|c| c != '\''), char('\'')),
+        braced,
+        take_while1(|c: char| !c.is_whitespace() && c != '>'),
+    ))(i)
+}
+// 值和值开始处剩余输入的长度, 引号不算在值里
+fn value_at(i: &str) -> IResult<&str, (usize, &str)> {
+    let quoted = i.starts_with('"') || i.starts_with('\'');
+    let (rest, value) = attr_value(i)?;
+    Ok((rest, (i.len() - quoted as usize, value)))
+}
+// 解析时位置先记录为剩余输入的长度, 在 open_tag 中转换
+fn attribute(i: &str) -> IResult<&str, Attr<'_>> {
+    let (rest, (name, value)) = pair(
+        attr_name,
+        opt(preceded(
+            delimited(multispace0, char('='), multispace0),
+            value_at,
+        )),
+    )(i)?;
+    Ok((
+        rest,
+        Attr {
+            name,
+            value: value.map(|(_, v)| v),
+            offset: i.len(),
+            value_offset: value.map_or(i.len(), |(at, _)| at),
+        },
+    ))
+}
+pub fn open_tag(input: &str) -> IResult<&str, Tag<'_>> {
+    let (i, name) = preceded(char('<'), tag_name)(input)?;
+    let (i, mut attrs) = many0(preceded(multispace0, alt((attribute, spread))))(i)?;
+    let (i, self_close) = preceded(multispace0, terminated(opt(char('/')), char('>')))(i)?;
+    for a in attrs.iter_mut() {
+        a.offset = input.len() - a.offset;
+        a.value_offset = input.len() - a.value_offset;
+    }
+    Ok((
+        i,
+        Tag {
+            name,
+            attrs,
+            self_close: self_close.is_some(),
+        },
+    ))
+}
+// {...props} 之类没有名字的属性
+fn spread(i: &str) -> IResult<&str, Attr<'_>> {
+    let (rest, value) = braced(i)?;
+    Ok((
+        rest,
+        Attr {
+            name: "",
+            value: Some(value),
+            offset: i.len(),
+            value_offset: i.len(),
+        },
+    ))
+}
+pub fn close_tag(i: &str) -> IResult<&str, &str> {
+    delimited(tag("</"), tag_name, preceded(multispace0, char('>')))(i)
+}
+// 跳过到 end 之后,找不到就直接到结尾
+pub fn skip_past<'a>(i: &'a str, end: &str) -> &'a str {
+    match i.find(end) {
+        Some(p) => &i[p + end.len()..],
+        None => "",
+    }
+}
+
+// 文档中所有的开始标签, 跳过注释和 script/style 的内容
+pub fn tags(content: &str) -> Vec<Tag<'_>> {
+    scan(content).into_iter().map(|(_, t)| t).collect()
+}
+// 开始标签和它结束位置的偏移
+fn scan(content: &str) -> Vec<(usize, Tag<'_>)> {
+    let mut rsl = vec![];
+    let mut i = content;
+    while let Some(t) = i.find('<') {
+        i = &i[t..];
+        if i.starts_with("<!--") {
+            i = skip_past(i, "-->");
+            continue;
+        }
+        if let Ok((rest, _)) = close_tag(i) {
+            i = rest;
+            continue;
+        }
+        if let Ok((rest, t)) = open_tag(i) {
+            let t = t.at(content.len() - i.len());
+            i = rest;
+            let end = content.len() - i.len();
+            if !t.self_close && (t.name == "script" || t.name == "style") {
+                i = skip_past(i, &format!("</{}", t.name));
+            }
+            rsl.push((end, t));
+            continue;
+        }
+        i = &i[1..];
+    }
+    rsl
+}
+
+// class 属性的原始值和它的位置, 不包括 {表达式}
+pub fn class_attrs<'a>(t: &Tag<'a>) -> Vec<(usize, &'a str)> {
+    t.attrs
+        .iter()
+        .filter(|a| a.name == "class")
+        .filter_map(|a| a.value.map(|v| (a.value_offset, v)))
+        .filter(|(_, v)| !v.starts_with('{'))
+        .collect()
+}
+
+fn style_block(css: &str) -> String {
+    format!("<style>\n{}\n{}{}\n</style>\n", STYLE_START, css, STYLE_END)
+}
+// 替换标记之间的内容, 返回 None 表示没有标记
+fn replace_marked(content: &str, css: &str) -> Option<String> {
+    let start = content.find(STYLE_START)? + STYLE_START.len();
+    let end = content[start..].find(STYLE_END)?;
+    Some(format!(
+        "{}\n{}{}",
+        &content[..start],
+        css,
+        &content[start + end..]
+    ))
+}
+
+// 只替换标记之间的内容, 没有标记就在文件末尾新增一个 <style>
+pub fn inject_style(content: &str, css: &str) -> String {
+    if let Some(rsl) = replace_marked(content, css) {
+        return rsl;
+    }
+    if css.is_empty() {
+        return content.to_string();
+    }
+    let mut rsl = content.to_string();
+    if !rsl.is_empty() && !rsl.ends_with('\n') {
+        rsl.push('\n');
+    }
+    format!("{}\n{}", rsl, style_block(css))
+}
+
+// svelte 只允许一个顶层 <style>, 已有时把标记加在它的末尾, 没有时和 inject_style 相同
+pub fn inject_existing_style(content: &str, css: &str) -> String {
+    if let Some(rsl) = replace_marked(content, css) {
+        return rsl;
+    }
+    let close = scan(content)
+        .into_iter()
+        .find(|(_, t)| t.name == "style" && !t.self_close)
+        .and_then(|(end, _)| content[end..].find("</style").map(|p| end + p));
+    match close {
+        Some(p) if !css.is_empty() => {
+            let before = &content[..p];
+            let newline = if before.ends_with('\n') { "" } else { "\n" };
+            format!(
+                "{}{}{}\n{}{}\n{}",
+                before,
+                newline,
+                STYLE_START,
+                css,
+                STYLE_END,
+                &content[p..]
+            )
+        }
+        _ => inject_style(content, css),
+    }
+}
+
+// html 文件的 <style> 放在 </head> 前面, 没有 head 时和 inject_style 相同
+pub fn inject_head(content: &str, css: &str) -> String {
+    if let Some(rsl) = replace_marked(content, css) {
+        return rsl;
+    }
+    match content.find("</head>") {
+        Some(p) if !css.is_empty() => {
+            format!("{}{}{}", &content[..p], style_block(css), &content[p..])
+        }
+        _ => inject_style(content, css),
+    }
+}
+
+#[test]
+fn test_tags() {
+    let content = "<!DOCTYPE html><html><head><script>if (a<b) {}</script></head>\
+                   <body><!-- <p class=\"x\"> --><div class=\" a  b \" id=main>\
+                   <Item {...props} className={ok ? 'c' : 'd'}/></div></body></html>";
+    let tags = tags(content);
+    assert_eq!(
+        tags.iter().map(|t| t.name).collect::<Vec<&str>>(),
+        vec!["html", "head", "script", "body", "div", "Item"]
+    );
+    assert_eq!(
+        tags[5]
+            .attrs
+            .iter()
+            .map(|a| (a.name, a.value))
+            .collect::<Vec<_>>(),
+        vec![
+            ("", Some("{...props}")),
+            ("className", Some("{ok ? 'c' : 'd'}"))
+        ]
+    );
+    // 位置指向文档中的原文
+    for a in tags.iter().flat_map(|t| t.attrs.iter()) {
+        assert!(content[a.offset..].starts_with(a.name));
+        assert!(content[a.value_offset..].starts_with(a.value.unwrap_or(a.name)));
+    }
+    assert_eq!(class_attrs(&tags[4]), vec![(102, " a  b ")]);
+}
+
+#[test]
+fn test_inject_style() {
+    let content = "<template><div class=\"h-1\"></div></template>\n";
+    let css = ".h-1{\nheight:1px;\n}\n";
+    let injected = inject_style(content, css);
+    assert_eq!(
+        injected,
+        format!(
+            "{}\n<style>\n{}\n{}{}\n</style>\n",
+            content, STYLE_START, css, STYLE_END
+        )
+    );
+    assert_eq!(inject_style(&injected, css), injected);
+    let handwritten = injected.replace("<template>", "<style>\n.a{}\n</style>\n<template>");
+    let updated = inject_style(&handwritten, ".h-2{\nheight:2px;\n}\n");
+    assert!(updated.starts_with("<style>\n.a{}\n</style>\n"));
+    assert!(updated.contains(".h-2{") && !updated.contains(".h-1{"));
+    assert_eq!(inject_style(content, ""), content);
+    let html = "<html><head></head><body></body></html>";
+    let injected = inject_head(html, css);
+    assert!(injected.starts_with(&format!("<html><head><style>\n{}", STYLE_START)));
+    assert!(injected.ends_with("</style>\n</head><body></body></html>"));
+    assert_eq!(inject_head(&injected, css), injected);
+}
@@ -1,2 +1,13 @@
+pub mod build;
+pub mod bundle;
+pub mod expr;
+pub mod extractor;
+pub mod html;
+pub mod jsx;
+pub mod markup;
+pub mod printer;
+pub mod resolve;
 pub mod signature;
+pub mod sourcemap;
+pub mod svelte;
 pub mod vue;
@@ -0,0 +1,142 @@
+use crate::config::reg::Span;
+use crate::replace::resolve::Rule;
+use serde_derive::{Deserialize, Serialize};
+use std::str::FromStr;
+
+// plain 为原来的格式, 每个声明一行不缩进
+#[derive(Debug, PartialEq, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Style {
+    #[default]
+    Plain,
+    Pretty,
+    Minified,
+}
+
+impl FromStr for Style {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(Style::Plain),
+            "pretty" => Ok(Style::Pretty),
+            "minified" => Ok(Style::Minified),
+            _ => Err(format!("unsupported style {}", s)),
+        }
+    }
+}
+
+// 一组选择器共用的声明块
+fn block(selectors: &[&str], declarations: &[(String, String)], style: Style) -> String {
+    let selector = selectors.join(",");
+    let mut rsl = String::new();
+    match style {
+        Style::Plain => {
+            rsl.push_str(&format!("{}{{\n", selector));
+            for (k, v) in declarations {
+                rsl.push_str(&format!("{}:{};\n", k, v));
+            }
+            rsl.push_str("}\n");
+        }
+        Style::Pretty => {
+            rsl.push_str(&format!("{} {{\n", selector));
+            for (k, v) in declarations {
+                rsl.push_str(&format!("  {}: {};\n", k, v));
+            }
+            rsl.push_str("}\n");
+        }
+        Style::Minified => {
+            let declarations = declarations
+                .iter()
+                .map(|(k, v)| format!("{}:{}", k, v))
+                .collect::<Vec<String>>();
+            rsl.push_str(&format!("{}{{{}}}", selector, declarations.join(";")));
+        }
+    }
+    rsl
+}
+
+fn shares_property(a: &[(String, String)], b: &[(String, String)]) -> bool {
+    a.iter().any(|(k, _)| b.iter().any(|(other, _)| k == other))
+}
+
+// 声明相同的一组选择器和其中的第一条规则
+type Group<'a> = (Vec<&'a str>, &'a Rule);
+
+// 声明完全相同的规则合并选择器, 中间有规则设置了相同属性时不合并, 避免改变覆盖顺序
+fn merge(rules: &[Rule]) -> Vec<Group<'_>> {
+    let mut rsl: Vec<Group<'_>> = vec![];
+    for rule in rules {
+        let mut target = None;
+        for (index, (_, first)) in rsl.iter().enumerate().rev() {
+            if first.declarations == rule.declarations {
+                target = Some(index);
+                break;
+            }
+            if shares_property(&first.declarations, &rule.declarations) {
+                break;
+            }
+        }
+        match target {
+            Some(index) => {
+                if !rsl[index].0.contains(&rule.selector.as_str()) {
+                    rsl[index].0.push(&rule.selector);
+                }
+            }
+            None => rsl.push((vec![&rule.selector], rule)),
+        }
+    }
+    rsl
+}
+
+// 同时返回每个声明块在输出中的偏移和生成它的规则的位置, 用于生成 source map
+pub fn print_mapped(rules: &[Rule], style: Style) -> (String, Vec<(usize, &Span)>) {
+    let groups = match style {
+        Style::Minified => merge(rules),
+        _ => rules
+            .iter()
+            .map(|r| (vec![r.selector.as_str()], r))
+            .collect(),
+    };
+    let mut rsl = String::new();
+    let mut mappings = vec![];
+    for (selectors, rule) in groups {
+        mappings.push((rsl.len(), &rule.span));
+        rsl.push_str(&block(&selectors, &rule.declarations, style));
+    }
+    (rsl, mappings)
+}
+
+pub fn print(rules: &[Rule], style: Style) -> String {
+    print_mapped(rules, style).0
+}
+
+#[test]
+fn test_print() {
+    let rule = |selector: &str, declarations: &[(&str, &str)]| Rule {
+        selector: selector.to_string(),
+        declarations: declarations
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+        span: Default::default(),
+    };
+    let rules = vec![
+        rule(".w-1", &[("width", "1px")]),
+        rule(".s-1", &[("width", "1px"), ("height", "1px")]),
+        rule(".x-1", &[("width", "1px")]),
+        rule(".h-1", &[("height", "1px")]),
+        rule(".y-1", &[("height", "1px")]),
+    ];
+    assert_eq!(
+        print(&rules[..2], Style::Pretty),
+        ".w-1 {\n  width: 1px;\n}\n.s-1 {\n  width: 1px;\n  height: 1px;\n}\n"
+    );
+    assert_eq!(print(&rules[..1], Style::Plain), ".w-1{\nwidth:1px;\n}\n");
+    // .x-1 和 .w-1 之间的 .s-1 也设置了 width, 不能合并
+    assert_eq!(
+        print(&rules, Style::Minified),
+        ".w-1{width:1px}.s-1{width:1px;height:1px}.x-1{width:1px}.h-1,.y-1{height:1px}"
+    );
+    assert_eq!("minified".parse::<Style>().unwrap(), Style::Minified);
+    assert!("compact".parse::<Style>().is_err());
+}
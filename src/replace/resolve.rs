@@ -0,0 +1,570 @@
+use crate::config::reg::{Signatures, Span, CSS};
+use crate::pkg::result::CommonResult;
+use crate::replace::signature::{arbitrary, escape, segments, signature};
+use regex::{Captures, Regex};
+use serde_derive::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Rule {
+    pub selector: String,
+    pub declarations: Vec<(String, String)>,
+    // 生成这条规则的选择器在规则文件中的位置
+    pub span: Span,
+}
+
+// 匹配到的一条规则, depth 为 ?extend 的层数, 0 是类本身
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Step {
+    pub depth: usize,
+    pub selector: String,
+    pub pattern: String,
+    pub span: Span,
+    pub args: BTreeMap<usize, String>,
+}
+
+// ?extend 最多展开的层数, 参数每层都变化的引用(.a-$1{ ?a-x$1 })不会出现重复的选择器
+const MAX_EXTEND_DEPTH: usize = 32;
+
+#[derive(Default)]
+struct Trace {
+    stack: Vec<String>,
+    steps: Vec<Step>,
+}
+
+// 规则选择器的某一段对应的参数位置: $1 或者 (1)
+fn arg_index(segment: &str) -> Option<usize> {
+    let index = if let Some(d) = segment.strip_prefix('$') {
+        d
+    } else if segment.starts_with('(') && segment.ends_with(')') {
+        &segment[1..segment.len() - 1]
+    } else {
+        return None;
+    };
+    index.parse().ok()
+}
+
+// 用规则选择器去匹配具体的类, 返回绑定的参数, 不是参数的段必须完全一致
+pub fn bind(pattern: &str, selector: &str) -> Option<HashMap<usize, String>> {
+    let patterns = segments(pattern);
+    let values = segments(selector);
+    if patterns.len() != values.len() || patterns[0] != values[0] {
+        return None;
+    }
+    let mut args = HashMap::new();
+    for (p, s) in patterns.iter().zip(values.iter()).skip(1) {
+        match arg_index(p) {
+            Some(index) => {
+                // 不合法的任意值不匹配任何规则
+                let value = if s.starts_with('[') {
+                    arbitrary(s)?
+                } else {
+                    s.to_string()
+                };
+                args.insert(index, value);
+            }
+            None if p != s => return None,
+            None => {}
+        }
+    }
+    Some(args)
+}
+
+// 不是参数的段越多, 规则越具体
+fn literal_count(pattern: &str) -> usize {
+    segments(pattern)
+        .into_iter()
+        .skip(1)
+        .filter(|p| arg_index(p).is_none())
+        .count()
+}
+
+// 1/2 => 50%, 最多保留6位小数
+fn percent(value: &str) -> Option<String> {
+    let (a, b) = value.split_once('/')?;
+    let (a, b) = (a.parse::<f64>().ok()?, b.parse::<f64>().ok()?);
+    if b == 0.0 {
+        return None;
+    }
+    Some(format!("{}%", (a / b * 1e8).round() / 1e6))
+}
+
+// 值本身带单位时(3px, auto, calc(..))忽略规则中 $1 后面的单位, .w-$1{width:$1px} 中 w-[3px] => width:3px
+// 分数后面有单位时换成百分比, w-1/2 => width:50%, 没有单位时不变, 如 aspect-ratio:1/2
+pub fn substitute(s: &str, args: &HashMap<usize, String>) -> CommonResult<String> {
+    let re = Regex::new(r"\$(\d+)([a-zA-Z%]*)")?;
+    for cap in re.captures_iter(s) {
+        let index: usize = cap[1].parse()?;
+        if !args.contains_key(&index) {
+            return Err(Box::from(format!("${} is not bound in {}", index, s)));
+        }
+    }
+    Ok(re
+        .replace_all(s, |cap: &Captures| {
+            let value = &args[&cap[1].parse::<usize>().unwrap()];
+            match value.chars().last() {
+                Some(c) if !c.is_ascii_digit() && c != '.' => value.clone(),
+                _ if cap[2].is_empty() => value.clone(),
+                _ => percent(value).unwrap_or_else(|| format!("{}{}", value, &cap[2])),
+            }
+        })
+        .to_string())
+}
+
+// 把规则展开成一条条的选择器, 子规则拼接在父选择器后面
+fn flatten(
+    selector: &str,
+    body: &CSS,
+    span: &Span,
+    args: &HashMap<usize, String>,
+    signatures: &Signatures,
+    trace: &mut Trace,
+    rsl: &mut Vec<Rule>,
+) -> CommonResult<()> {
+    let mut rule = Rule {
+        selector: selector.to_string(),
+        declarations: vec![],
+        span: span.clone(),
+    };
+    let mut children = vec![];
+    if let CSS::Object(d) = body {
+        for (k, v, child_span) in d {
+            match v {
+                CSS::Value(value) => rule
+                    .declarations
+                    .push((substitute(k, args)?, substitute(value, args)?)),
+                CSS::ExtendValue(reference) => rule.declarations.extend(extend(
+                    &substitute(reference, args)?,
+                    signatures,
+                    trace,
+                )?),
+                CSS::Object(_) => children.push((
+                    format!("{} {}", selector, substitute(k, args)?),
+                    v,
+                    child_span,
+                )),
+                _ => {}
+            }
+        }
+    }
+    if !rule.declarations.is_empty() {
+        rsl.push(rule);
+    }
+    for (child_selector, child, child_span) in children {
+        flatten(
+            &child_selector,
+            child,
+            child_span,
+            args,
+            signatures,
+            trace,
+            rsl,
+        )?;
+    }
+    Ok(())
+}
+
+// ?w-$1 引用的规则, 取出它自身的声明内联进来
+fn extend(
+    reference: &str,
+    signatures: &Signatures,
+    trace: &mut Trace,
+) -> CommonResult<Vec<(String, String)>> {
+    let selector = format!(".{}", reference.trim_start_matches('.'));
+    if trace.stack.contains(&selector) {
+        trace.stack.push(selector);
+        return Err(Box::from(format!(
+            "extend cycle: {}",
+            trace.stack.join(" -> ")
+        )));
+    }
+    if trace.stack.len() >= MAX_EXTEND_DEPTH {
+        trace.stack.push(selector);
+        return Err(Box::from(format!(
+            "extend deeper than {}: {}",
+            MAX_EXTEND_DEPTH,
+            trace.stack.join(" -> ")
+        )));
+    }
+    let rules = match resolve_selector(&selector, signatures, trace)? {
+        None => {
+            return Err(Box::from(format!(
+                "extend {} not found, from {}",
+                selector,
+                trace.stack.join(" -> ")
+            )))
+        }
+        Some(d) => d,
+    };
+    let escaped = escape(&selector);
+    Ok(rules
+        .into_iter()
+        .filter(|r| r.selector == escaped)
+        .flat_map(|r| r.declarations)
+        .collect())
+}
+
+fn resolve_selector(
+    selector: &str,
+    signatures: &Signatures,
+    trace: &mut Trace,
+) -> CommonResult<Option<Vec<Rule>>> {
+    let candidates = match signatures.get(&signature(selector)) {
+        None => return Ok(None),
+        Some(d) => d
+            .iter()
+            .filter_map(|(pattern, body, span)| {
+                bind(pattern, selector)
+                    .map(|args| (literal_count(pattern), args, pattern, body, span))
+            })
+            .collect::<Vec<_>>(),
+    };
+    let most_literal = match candidates.iter().map(|c| c.0).max() {
+        None => return Ok(None),
+        Some(c) => c,
+    };
+    let depth = trace.stack.len();
+    trace.stack.push(selector.to_string());
+    let escaped = escape(selector);
+    let mut rsl = vec![];
+    for (_, args, pattern, body, span) in candidates.iter().filter(|c| c.0 == most_literal) {
+        trace.steps.push(Step {
+            depth,
+            selector: selector.to_string(),
+            pattern: pattern.to_string(),
+            span: (*span).clone(),
+            args: args.clone().into_iter().collect(),
+        });
+        flatten(&escaped, body, span, args, signatures, trace, &mut rsl)?;
+    }
+    trace.stack.pop();
+    Ok(Some(rsl))
+}
+
+pub fn resolve_class(class: &str, signatures: &Signatures) -> CommonResult<Option<Vec<Rule>>> {
+    let selector = format!(".{}", class.trim_start_matches('.'));
+    resolve_selector(&selector, signatures, &mut Default::default())
+}
+
+// 和 resolve_class 相同, 同时返回匹配规则和展开 ?extend 的每一步
+pub fn explain_class(
+    class: &str,
+    signatures: &Signatures,
+) -> CommonResult<Option<(Vec<Step>, Vec<Rule>)>> {
+    let selector = format!(".{}", class.trim_start_matches('.'));
+    let mut trace = Trace::default();
+    Ok(resolve_selector(&selector, signatures, &mut trace)?.map(|rules| (trace.steps, rules)))
+}
+
+// 编辑距离
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<char>>();
+    let mut prev = (0..=b.len()).collect::<Vec<usize>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur.push((prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1));
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+// 没有匹配时相近的规则, 签名相同但字面段不同的排在最前面
+pub fn similar(class: &str, signatures: &Signatures) -> Vec<String> {
+    let selector = format!(".{}", class.trim_start_matches('.'));
+    let sig = signature(&selector);
+    let max = (sig.len() / 3).max(2);
+    let mut rsl = signatures
+        .iter()
+        .map(|(s, rules)| (distance(&sig, s), rules))
+        .filter(|(d, _)| *d <= max)
+        .flat_map(|(d, rules)| rules.iter().map(move |(pattern, _, _)| (d, pattern)))
+        .collect::<Vec<_>>();
+    rsl.sort();
+    rsl.into_iter().take(5).map(|(_, p)| p.clone()).collect()
+}
+
+#[test]
+fn test_resolve_class() {
+    use crate::config::reg::parse;
+    use crate::replace::printer::{print, Style};
+    let (_, css) = parse(
+        "
+        .h-$1{
+            height:$1px;
+        }
+        .w-full{
+            width:100%;
+        }
+        .w-$1{
+            width:$1px;
+        }
+        .t-$1-$2{
+            .c{
+                width:$2px;
+            }
+        }
+        .ba-(2)-(1){
+            border:$1px solid #$2;
+        }
+        .m-$1{
+            margin:$2px;
+        }
+    ",
+    )
+    .ok()
+    .unwrap();
+    let signatures = css.get_signature().unwrap();
+    let rules = resolve_class("h-12", &signatures).unwrap().unwrap();
+    assert_eq!(print(&rules, Style::Plain), ".h-12{\nheight:12px;\n}\n");
+    let rules = resolve_class("t-1-2", &signatures).unwrap().unwrap();
+    assert_eq!(print(&rules, Style::Plain), ".t-1-2 .c{\nwidth:2px;\n}\n");
+    let rules = resolve_class("ba-fff-2", &signatures).unwrap().unwrap();
+    assert_eq!(
+        print(&rules, Style::Plain),
+        ".ba-fff-2{\nborder:2px solid #fff;\n}\n"
+    );
+    let rules = resolve_class("w-full", &signatures).unwrap().unwrap();
+    assert_eq!(print(&rules, Style::Plain), ".w-full{\nwidth:100%;\n}\n");
+    let rules = resolve_class("w-10", &signatures).unwrap().unwrap();
+    assert_eq!(print(&rules, Style::Plain), ".w-10{\nwidth:10px;\n}\n");
+    assert!(resolve_class("m-1", &signatures).is_err());
+    assert_eq!(resolve_class("tcp", &signatures).unwrap(), None);
+}
+
+#[test]
+fn test_resolve_extend() {
+    use crate::config::reg::parse;
+    let (_, css) = parse(
+        "
+        .w-$1{
+            width:$1px;
+        }
+        .h-$1{
+            height:$1px;
+        }
+        .s-$1-$2{
+            ?w-$1;
+            ?h-$2;
+            display:block;
+        }
+        .card-$1{
+            .title{
+                ?s-$1-$1;
+            }
+            .body{
+                ?h-$1
+            }
+        }
+        .a-$1{
+            ?b-$1
+        }
+        .b-$1{
+            ?c-$1
+        }
+        .c-$1{
+            ?a-$1
+        }
+        .d-$1{
+            ?none-$1
+        }
+        .e-$1{
+            ?e-x$1
+        }
+    ",
+    )
+    .ok()
+    .unwrap();
+    let signatures = css.get_signature().unwrap();
+    let rules = resolve_class("card-4", &signatures).unwrap().unwrap();
+    assert_eq!(
+        rules,
+        vec![
+            Rule {
+                selector: ".card-4 .title".to_string(),
+                declarations: vec![
+                    ("width".to_string(), "4px".to_string()),
+                    ("height".to_string(), "4px".to_string()),
+                    ("display".to_string(), "block".to_string()),
+                ],
+                span: rules[0].span.clone(),
+            },
+            Rule {
+                selector: ".card-4 .body".to_string(),
+                declarations: vec![("height".to_string(), "4px".to_string())],
+                span: rules[1].span.clone(),
+            },
+        ]
+    );
+    let e = resolve_class("a-1", &signatures).unwrap_err();
+    assert_eq!(e.to_string(), "extend cycle: .a-1 -> .b-1 -> .c-1 -> .a-1");
+    let e = resolve_class("d-1", &signatures).unwrap_err();
+    assert_eq!(e.to_string(), "extend .none-1 not found, from .d-1");
+    // 每一层的参数都不同, 靠层数限制结束
+    let e = resolve_class("e-1", &signatures).unwrap_err().to_string();
+    assert!(e.starts_with("extend deeper than 32: .e-1 -> .e-x1 -> .e-xx1 -> "));
+    assert_eq!(e.matches(" -> ").count(), 32);
+}
+
+#[test]
+fn test_explain_class() {
+    use crate::config::reg::parse;
+    use crate::replace::printer::{print, Style};
+    let (_, css) = parse(
+        "
+        .w-$1{
+            width:$1px;
+        }
+        .s-$1-$2{
+            ?w-$2;
+        }
+    ",
+    )
+    .ok()
+    .unwrap();
+    let signatures = css.get_signature().unwrap();
+    let (steps, rules) = explain_class("s-1-2", &signatures).unwrap().unwrap();
+    assert_eq!(
+        steps
+            .iter()
+            .map(|s| (s.depth, s.selector.as_str(), s.pattern.as_str()))
+            .collect::<Vec<_>>(),
+        vec![(0, ".s-1-2", ".s-$1-$2"), (1, ".w-2", ".w-$1")]
+    );
+    assert_eq!(steps[0].args[&2], "2");
+    assert_eq!(print(&rules, Style::Plain), ".s-1-2{\nwidth:2px;\n}\n");
+    assert_eq!(explain_class("ss-1", &signatures).unwrap(), None);
+    assert_eq!(similar("ss-1", &signatures), vec![".w-$1"]);
+    assert_eq!(similar("s-1-2-3", &signatures), vec![".s-$1-$2"]);
+    assert!(similar("border-1", &signatures).is_empty());
+}
+
+#[test]
+fn test_resolve_numbers() {
+    use crate::config::reg::parse;
+    use crate::replace::printer::{print, Style};
+    let (_, css) = parse(
+        "
+        .m-$1{
+            margin:$1px;
+        }
+        .-m-$1{
+            margin:-$1px;
+        }
+        .w-$1{
+            width:$1rem;
+            ?-m-$1;
+        }
+        .aspect-$1{
+            aspect-ratio:$1;
+        }
+    ",
+    )
+    .ok()
+    .unwrap();
+    let signatures = css.get_signature().unwrap();
+    let rules = resolve_class("-m-4", &signatures).unwrap().unwrap();
+    assert_eq!(print(&rules, Style::Plain), ".-m-4{\nmargin:-4px;\n}\n");
+    let rules = resolve_class("w-1.5", &signatures).unwrap().unwrap();
+    assert_eq!(
+        print(&rules, Style::Plain),
+        ".w-1\\.5{\nwidth:1.5rem;\nmargin:-1.5px;\n}\n"
+    );
+    let rules = resolve_class("w-1/2", &signatures).unwrap().unwrap();
+    assert_eq!(rules[0].selector, ".w-1\\/2");
+    assert_eq!(
+        rules[0].declarations,
+        vec![
+            ("width".to_string(), "50%".to_string()),
+            ("margin".to_string(), "-50%".to_string())
+        ]
+    );
+    let rules = resolve_class("w-1/3", &signatures).unwrap().unwrap();
+    assert_eq!(rules[0].declarations[0].1, "33.333333%");
+    let rules = resolve_class("aspect-16/9", &signatures).unwrap().unwrap();
+    assert_eq!(rules[0].declarations[0].1, "16/9");
+}
+
+#[test]
+fn test_resolve_arbitrary() {
+    use crate::config::reg::parse;
+    use crate::replace::printer::{print, Style};
+    let (_, css) = parse(
+        "
+        .w-$1{
+            width:$1;
+        }
+        .c-$1{
+            color:$1;
+        }
+        .grid-cols-$1{
+            grid-template-columns:$1;
+        }
+    ",
+    )
+    .ok()
+    .unwrap();
+    let signatures = css.get_signature().unwrap();
+    let rules = resolve_class("w-[123px]", &signatures).unwrap().unwrap();
+    assert_eq!(
+        print(&rules, Style::Plain),
+        ".w-\\[123px\\]{\nwidth:123px;\n}\n"
+    );
+    let rules = resolve_class("c-[#ff00aa]", &signatures).unwrap().unwrap();
+    assert_eq!(
+        print(&rules, Style::Plain),
+        ".c-\\[\\#ff00aa\\]{\ncolor:#ff00aa;\n}\n"
+    );
+    let rules = resolve_class("grid-cols-[1fr_2fr]", &signatures)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        print(&rules, Style::Plain),
+        ".grid-cols-\\[1fr_2fr\\]{\ngrid-template-columns:1fr 2fr;\n}\n"
+    );
+    let rules = resolve_class("w-[calc(100%-2px)]", &signatures)
+        .unwrap()
+        .unwrap();
+    assert_eq!(rules[0].declarations[0].1, "calc(100%-2px)");
+    // 不合法的任意值不生成规则
+    for class in &[
+        "c-[red}body{display:none]",
+        "w-[1px;x:y]",
+        "w-[calc(1px]",
+        "c-[</style><script>alert(1)</script>]",
+        "c-[/*_fcss:end_*/]",
+    ] {
+        assert!(resolve_class(class, &signatures).unwrap().is_none());
+    }
+}
+
+#[test]
+fn test_resolve_unit() {
+    use crate::config::reg::parse;
+    use crate::replace::printer::{print, Style};
+    let (_, css) = parse(
+        "
+        .h-$1{
+            height:$1px;
+        }
+    ",
+    )
+    .ok()
+    .unwrap();
+    let signatures = css.get_signature().unwrap();
+    let rules = resolve_class("h-3", &signatures).unwrap().unwrap();
+    assert_eq!(print(&rules, Style::Plain), ".h-3{\nheight:3px;\n}\n");
+    let rules = resolve_class("h-[3px]", &signatures).unwrap().unwrap();
+    assert_eq!(
+        print(&rules, Style::Plain),
+        ".h-\\[3px\\]{\nheight:3px;\n}\n"
+    );
+    let rules = resolve_class("h-[50%]", &signatures).unwrap().unwrap();
+    assert_eq!(rules[0].declarations[0].1, "50%");
+    let rules = resolve_class("h-[calc(100%_-_2px)]", &signatures)
+        .unwrap()
+        .unwrap();
+    assert_eq!(rules[0].declarations[0].1, "calc(100% - 2px)");
+}
@@ -1,29 +1,99 @@
 use crate::pkg::result::CommonResult;
 use std::collections::HashSet;
 
+// 类名按 - 分段, 开头的 . 和表示负值的 - 属于第一段, 参数中的小数点和分数的 / 不拆开,
+// [] 中的任意值是一段, 其中的 - 也不拆开, 如 .w-[calc(100%-2px)]
+pub fn segments(selector: &str) -> Vec<&str> {
+    let dot = selector.len() - selector.trim_start_matches('.').len();
+    let head = if selector[dot..].starts_with('-') {
+        dot + 1
+    } else {
+        dot
+    };
+    let mut rsl = vec![];
+    let mut start = 0;
+    let mut depth = 0;
+    for (i, c) in selector.char_indices().skip_while(|(i, _)| *i < head) {
+        match c {
+            '[' => depth += 1,
+            ']' if depth > 0 => depth -= 1,
+            '-' if depth == 0 => {
+                rsl.push(&selector[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    rsl.push(&selector[start..]);
+    rsl
+}
+
+// 括号是否配对
+fn balanced(value: &str) -> bool {
+    let mut stack = vec![];
+    for c in value.chars() {
+        match c {
+            '(' | '[' => stack.push(c),
+            ')' if stack.pop() != Some('(') => return false,
+            ']' if stack.pop() != Some('[') => return false,
+            _ => {}
+        }
+    }
+    stack.is_empty()
+}
+
+// 任意值中允许的字符, ; { } < > \ 引号和注释会结束当前的声明, 规则或者 <style>
+fn allowed(value: &str) -> bool {
+    value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "#%.,()[]_-+*/".contains(c))
+        && !value.contains("/*")
+        && !value.contains("*/")
+}
+
+// [1fr_2fr] 形式的参数去掉括号, _ 换成空格, 为空, 有不允许的字符或者括号不配对时返回 None
+pub fn arbitrary(segment: &str) -> Option<String> {
+    let value = segment.strip_prefix('[')?.strip_suffix(']')?;
+    if value.is_empty() || !allowed(value) || !balanced(value) {
+        return None;
+    }
+    Some(value.replace('_', " "))
+}
+
+// 选择器中除了开头的 . 以外, 不能直接出现在类名中的字符加上转义, 如 .w-1/2 => .w-1\/2
+pub fn escape(selector: &str) -> String {
+    let dot = selector.len() - selector.trim_start_matches('.').len();
+    let mut rsl = selector[..dot].to_string();
+    for c in selector[dot..].chars() {
+        if !(c.is_alphanumeric() || c == '-' || c == '_' || !c.is_ascii()) {
+            rsl.push('\\');
+        }
+        rsl.push(c);
+    }
+    rsl
+}
+
+// .h-12 => .h-$1, 第一段之后的每一段都作为参数
+pub fn signature(one_css: &str) -> String {
+    segments(one_css)
+        .into_iter()
+        .enumerate()
+        .map(|(index, d)| {
+            if index == 0 {
+                d.to_string()
+            } else {
+                format!("${}", index)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("-")
+}
+
 pub fn class_to_signature(cls: Vec<String>) -> CommonResult<HashSet<String>> {
     let mut rsl: HashSet<String> = Default::default();
     for class_one_line in cls {
-        let multi_css = class_one_line
-            .split(" ")
-            .filter(|c| !c.is_empty())
-            .collect::<Vec<&str>>();
-        for one_css in multi_css {
-            let mut index = -1;
-            rsl.insert(
-                one_css
-                    .split("-")
-                    .map(|d| {
-                        index += 1;
-                        if index == 0 {
-                            return d.to_string();
-                        } else {
-                            return format!("${}", index);
-                        }
-                    })
-                    .collect::<Vec<String>>()
-                    .join("-"),
-            );
+        for one_css in class_one_line.split_whitespace() {
+            rsl.insert(signature(one_css));
         }
     }
     Ok(rsl)
@@ -37,3 +107,48 @@ fn test_class_to_signature() {
     ];
     dbg!(class_to_signature(in_param).unwrap());
 }
+
+#[test]
+fn test_segments() {
+    assert_eq!(segments(".-m-4"), vec![".-m", "4"]);
+    assert_eq!(segments("-m-4"), vec!["-m", "4"]);
+    assert_eq!(segments(".w-1.5"), vec![".w", "1.5"]);
+    assert_eq!(segments(".w-1/2"), vec![".w", "1/2"]);
+    assert_eq!(segments(".tcp"), vec![".tcp"]);
+    assert_eq!(signature(".-m-4"), ".-m-$1");
+    assert_eq!(signature(".-m-$1"), ".-m-$1");
+    assert_eq!(signature(".b-1-fff"), ".b-$1-$2");
+    assert_eq!(escape(".w-1.5"), ".w-1\\.5");
+    assert_eq!(escape(".w-1/2"), ".w-1\\/2");
+    assert_eq!(escape(".-m-4"), ".-m-4");
+    assert_eq!(
+        segments(".grid-cols-[1fr_2fr]"),
+        vec![".grid", "cols", "[1fr_2fr]"]
+    );
+    assert_eq!(
+        segments(".w-[calc(100%-2px)]"),
+        vec![".w", "[calc(100%-2px)]"]
+    );
+    assert_eq!(signature(".c-[#ff00aa]"), ".c-$1");
+    assert_eq!(arbitrary("[1fr_2fr]"), Some("1fr 2fr".to_string()));
+    assert_eq!(arbitrary("12"), None);
+    assert_eq!(
+        arbitrary("[calc(100%_-_2px)]"),
+        Some("calc(100% - 2px)".to_string())
+    );
+    assert_eq!(arbitrary("[]"), None);
+    assert_eq!(arbitrary("[1px;x:y]"), None);
+    assert_eq!(arbitrary("[red}body{display:none]"), None);
+    assert_eq!(arbitrary("[calc(1px]"), None);
+    assert_eq!(arbitrary("[a]]"), None);
+    assert_eq!(arbitrary("[</style><script>alert(1)</script>]"), None);
+    assert_eq!(arbitrary("[/*_fcss:end_*/]"), None);
+    assert_eq!(arbitrary("[1px*/]"), None);
+    assert_eq!(arbitrary("['a']"), None);
+    assert_eq!(arbitrary("[a\\b]"), None);
+    assert_eq!(
+        arbitrary("[calc(100%/3*2)]"),
+        Some("calc(100%/3*2)".to_string())
+    );
+    assert_eq!(escape(".c-[#ff00aa]"), ".c-\\[\\#ff00aa\\]");
+}
@@ -0,0 +1,172 @@
+use crate::config::reg::Span;
+use serde_json::json;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// base64 VLQ, 最低位是符号位
+fn vlq(value: i64, rsl: &mut String) {
+    let mut v = if value < 0 {
+        ((-value) << 1) | 1
+    } else {
+        value << 1
+    } as u64;
+    loop {
+        let mut digit = v & 31;
+        v >>= 5;
+        if v > 0 {
+            digit |= 32;
+        }
+        rsl.push(BASE64[digit as usize] as char);
+        if v == 0 {
+            break;
+        }
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    match fs::canonicalize(path) {
+        Ok(p) => p,
+        Err(_) => env::current_dir()
+            .map(|d| d.join(path))
+            .unwrap_or_else(|_| path.to_path_buf()),
+    }
+}
+
+// to 相对于 dir 的路径, 用 / 分隔
+fn relative(dir: &Path, to: &Path) -> String {
+    let dir = absolute(dir);
+    let to = absolute(to);
+    let from = dir.components().collect::<Vec<Component<'_>>>();
+    let to = to.components().collect::<Vec<Component<'_>>>();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut rsl = PathBuf::new();
+    for _ in common..from.len() {
+        rsl.push("..");
+    }
+    for c in &to[common..] {
+        rsl.push(c);
+    }
+    rsl.to_string_lossy().replace('\\', "/")
+}
+
+// v3 source map, mappings 是 print_mapped 返回的偏移和规则位置, 按偏移排序, 路径相对于 map 文件所在目录
+pub fn source_map(
+    css: &str,
+    mappings: &[(usize, &Span)],
+    css_path: &Path,
+    map_path: &Path,
+) -> String {
+    let dir = match map_path.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d,
+        _ => Path::new("."),
+    };
+    let mut sources: Vec<String> = vec![];
+    let mut rsl = String::new();
+    let mut line = 0;
+    // 上一个片段的 生成列, 源文件, 源行, 源列
+    let mut prev = (0, 0, 0, 0);
+    // 已经数过的偏移和它的行列, 每个片段从上一个位置接着数
+    let (mut at, mut l, mut c) = (0, 1, 1);
+    for (offset, span) in mappings {
+        if span.file.is_empty() {
+            continue;
+        }
+        for ch in css[at..*offset].chars() {
+            if ch == '\n' {
+                l += 1;
+                c = 1;
+            } else {
+                c += 1;
+            }
+        }
+        at = *offset;
+        while line < l - 1 {
+            rsl.push(';');
+            line += 1;
+            prev.0 = 0;
+        }
+        if !rsl.is_empty() && !rsl.ends_with(';') {
+            rsl.push(',');
+        }
+        let source = relative(dir, Path::new(&span.file));
+        let index = match sources.iter().position(|s| *s == source) {
+            Some(i) => i,
+            None => {
+                sources.push(source);
+                sources.len() - 1
+            }
+        } as i64;
+        let current = (
+            c as i64 - 1,
+            index,
+            span.line as i64 - 1,
+            span.column as i64 - 1,
+        );
+        vlq(current.0 - prev.0, &mut rsl);
+        vlq(current.1 - prev.1, &mut rsl);
+        vlq(current.2 - prev.2, &mut rsl);
+        vlq(current.3 - prev.3, &mut rsl);
+        prev = current;
+    }
+    let file = css_path
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+    json!({
+        "version": 3,
+        "file": file,
+        "sources": sources,
+        "names": [],
+        "mappings": rsl,
+    })
+    .to_string()
+}
+
+#[test]
+fn test_source_map() {
+    use crate::config::reg::load;
+    use crate::replace::printer::{print_mapped, Style};
+    use crate::replace::resolve::resolve_class;
+    let mut rsl = String::new();
+    for v in &[0, 1, -1, 16, 123] {
+        vlq(*v, &mut rsl);
+    }
+    assert_eq!(rsl, "ACDgB2H");
+    assert_eq!(
+        relative(Path::new("/a/b"), Path::new("/a/c/d.reg")),
+        "../c/d.reg"
+    );
+    let css = load(
+        "@import(./reg/rules/size.reg);\n@import(./reg/rules/color.reg);",
+        "./res/test/config.json",
+        &[],
+    )
+    .unwrap();
+    let signatures = css.get_signature().unwrap();
+    let mut rules = vec![];
+    for class in &["h-1", "c-fff", "w-2"] {
+        rules.extend(resolve_class(class, &signatures).unwrap().unwrap());
+    }
+    let (out, mappings) = print_mapped(&rules, Style::Plain);
+    let map = source_map(
+        &out,
+        &mappings,
+        Path::new("res/test/fcss.css"),
+        Path::new("res/test/fcss.css.map"),
+    );
+    let map: serde_json::Value = serde_json::from_str(&map).unwrap();
+    assert_eq!(map["file"], "fcss.css");
+    assert_eq!(
+        map["sources"],
+        json!(["reg/rules/size.reg", "reg/rules/color.reg"])
+    );
+    // .h-1 在 size.reg 第4行, .c-fff 在 color.reg 第1行, .w-2 在 size.reg 第1行
+    assert_eq!(map["mappings"], "AAGA;;;ACHA;;;ADAA");
+    let (out, mappings) = print_mapped(&rules, Style::Minified);
+    let map = source_map(&out, &mappings, Path::new("a.css"), Path::new("a.css.map"));
+    let map: serde_json::Value = serde_json::from_str(&map).unwrap();
+    assert_eq!(map["mappings"], "AAGA,gBCHA,kBDAA");
+}
@@ -0,0 +1,93 @@
+use crate::pkg::result::CommonResult;
+use crate::replace::expr::{bind_class_offsets, matching_close, words};
+use crate::replace::extractor::{located, Class, Extractor};
+use crate::replace::markup::{inject_existing_style, tags};
+
+// class="a {cond ? 'b' : ''}" 中的静态部分和 {} 里的表达式, 以及它们在值中的位置
+fn class_value(value: &str) -> Vec<(usize, String)> {
+    let shift = |offset: usize, names: Vec<(usize, String)>| {
+        names.into_iter().map(move |(at, c)| (offset + at, c))
+    };
+    let mut rsl = vec![];
+    let mut base = 0;
+    while let Some(start) = value[base..].find('{').map(|s| base + s) {
+        rsl.extend(shift(base, words(&value[base..start])));
+        match matching_close(&value[start..]) {
+            Some(end) => {
+                let expr = &value[start + 1..start + end];
+                rsl.extend(shift(start + 1, bind_class_offsets(expr)));
+                base = start + end + 1;
+            }
+            None => return rsl,
+        }
+    }
+    rsl.extend(shift(base, words(&value[base..])));
+    rsl
+}
+
+pub struct Svelte;
+
+impl Extractor for Svelte {
+    fn classes(&self, content: &str) -> CommonResult<Vec<Class>> {
+        let mut rsl = vec![];
+        for t in tags(content) {
+            for a in t.attrs {
+                match (a.name.strip_prefix("class:"), a.value) {
+                    // class:name={cond}
+                    (Some(c), _) => rsl.push(Class {
+                        offset: a.offset + "class:".len(),
+                        name: c.to_string(),
+                    }),
+                    (None, Some(v)) if a.name == "class" => {
+                        rsl.extend(located(a.value_offset, class_value(v)))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(rsl)
+    }
+    fn inject(&self, content: &str, css: &str) -> Option<String> {
+        Some(inject_existing_style(content, css))
+    }
+}
+
+#[test]
+fn test_svelte() {
+    let content = std::fs::read_to_string("./res/test/svelte/demo.svelte").unwrap();
+    let classes = Svelte.classes(&content).unwrap();
+    assert_eq!(
+        classes
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<&str>>(),
+        vec!["h-12", "w-12", "c-fff", "c-000", "p-2", "w-24"]
+    );
+    for c in classes {
+        assert_eq!(&content[c.offset..c.offset + c.name.len()], c.name);
+    }
+}
+
+#[test]
+fn test_svelte_inject() {
+    use crate::config::reg::parse;
+    use crate::replace::extractor::generate;
+    use crate::replace::markup::{STYLE_END, STYLE_START};
+    use crate::replace::printer::Style;
+    let (_, css) = parse(".h-$1{\nheight:$1px;\n}").ok().unwrap();
+    let signatures = css.get_signature().unwrap();
+    let content = std::fs::read_to_string("./res/test/svelte/demo.svelte").unwrap();
+    let (css, _) = generate(&content, &Svelte, &signatures, Style::Plain).unwrap();
+    let injected = Svelte.inject(&content, &css).unwrap();
+    // 生成的样式放进已有的 <style>, 不能再新增一个
+    assert_eq!(injected.matches("<style").count(), 1);
+    assert!(injected.ends_with(&format!(
+        "    width: 10px;\n  }}\n{}\n.h-12{{\nheight:12px;\n}}\n{}\n</style>\n",
+        STYLE_START, STYLE_END
+    )));
+    assert_eq!(Svelte.inject(&injected, &css).unwrap(), injected);
+    let without = content[..content.find("<style>").unwrap()].to_string();
+    let injected = Svelte.inject(&without, &css).unwrap();
+    assert_eq!(injected.matches("<style").count(), 1);
+    assert!(injected.starts_with(&without));
+}
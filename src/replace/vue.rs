@@ -1 +1,123 @@
+use crate::pkg::result::CommonResult;
+use crate::replace::expr::{bind_class_offsets, words};
+use crate::replace::extractor::{located, Class, Extractor};
+use crate::replace::markup::{class_attrs, close_tag, inject_style, open_tag, skip_past, Tag};
 
+// 返回最外层 <template> 中的所有标签, script/style 等其他块不会进入
+pub fn template_tags(content: &str) -> CommonResult<Vec<Tag<'_>>> {
+    let mut tags = vec![];
+    let mut depth = 0;
+    let mut i = content;
+    loop {
+        let next_tag = i.find('<');
+        let next_mustache = i.find("{{").filter(|_| depth > 0);
+        match (next_tag, next_mustache) {
+            (None, _) => break,
+            (Some(t), Some(m)) if m < t => {
+                i = skip_past(&i[m..], "}}");
+                continue;
+            }
+            (Some(t), _) => i = &i[t..],
+        };
+        if i.starts_with("<!--") {
+            i = skip_past(i, "-->");
+            continue;
+        }
+        if let Ok((rest, name)) = close_tag(i) {
+            i = rest;
+            if name == "template" && depth > 0 {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(tags);
+                }
+            }
+            continue;
+        }
+        if let Ok((rest, t)) = open_tag(i) {
+            let t = t.at(content.len() - i.len());
+            i = rest;
+            if t.self_close {
+                if depth > 0 {
+                    tags.push(t);
+                }
+                continue;
+            }
+            match t.name {
+                "template" => {
+                    depth += 1;
+                    if depth > 1 {
+                        tags.push(t);
+                    }
+                }
+                "script" | "style" => {
+                    i = skip_past(i, &format!("</{}", t.name));
+                }
+                _ if depth == 0 => {
+                    // 顶层的自定义块(i18n之类)不解析
+                    i = skip_past(i, &format!("</{}", t.name));
+                }
+                _ => tags.push(t),
+            }
+            continue;
+        }
+        i = &i[1..];
+    }
+    if depth > 0 {
+        return Err(Box::from("<template> is not closed"));
+    }
+    Ok(tags)
+}
+
+// :class 和 v-bind:class 绑定的表达式和它的位置
+fn bind_class_attrs<'a>(t: &Tag<'a>) -> Vec<(usize, &'a str)> {
+    t.attrs
+        .iter()
+        .filter(|a| a.name == ":class" || a.name == "v-bind:class")
+        .filter_map(|a| a.value.map(|v| (a.value_offset, v)))
+        .collect()
+}
+
+pub struct Vue;
+
+impl Extractor for Vue {
+    fn classes(&self, content: &str) -> CommonResult<Vec<Class>> {
+        let tags = template_tags(content)?;
+        let mut rsl = vec![];
+        for (offset, value) in tags.iter().flat_map(class_attrs) {
+            rsl.extend(located(offset, words(value)));
+        }
+        for (offset, value) in tags.iter().flat_map(bind_class_attrs) {
+            rsl.extend(located(offset, bind_class_offsets(value)));
+        }
+        Ok(rsl)
+    }
+    fn inject(&self, content: &str, css: &str) -> Option<String> {
+        Some(inject_style(content, css))
+    }
+}
+
+#[test]
+fn test_vue_classes() {
+    let content = std::fs::read_to_string("./res/test/vue/demo.vue").unwrap();
+    let classes = Vue.classes(&content).unwrap();
+    // 先是 class 中的类, 然后是 :class 绑定的
+    assert_eq!(
+        classes
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<&str>>(),
+        vec![
+            "h-12", "w-12", "b-1-fff", "tcp", "hw-12-21", "p-2", "w-24", "h-4", "p-4", "c-fff",
+            "c-000"
+        ]
+    );
+    // 类名的位置指向文件中的原文
+    for c in classes {
+        assert_eq!(&content[c.offset..c.offset + c.name.len()], c.name);
+    }
+    assert!(Vue.classes("<template><div class=\"a\"></div>").is_err());
+    assert!(Vue
+        .classes("<script>const a = '<template>'</script>")
+        .unwrap()
+        .is_empty());
+}
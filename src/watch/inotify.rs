@@ -0,0 +1,283 @@
+use crate::pkg::dir::walk_dir;
+use crate::pkg::ignore::{root_of, Filter};
+use crate::pkg::result::CommonResult;
+use crate::watch::watch::{is_watched, EventKind, WatchEvent, Watcher};
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub struct InotifyWatcher {
+    file_types: Vec<String>,
+    file_notify: Inotify,
+    filter: Filter,
+    roots: Vec<PathBuf>,
+    // inotify 事件只有文件名, 通过监听描述符找到所在目录
+    dirs: HashMap<WatchDescriptor, PathBuf>,
+    files: HashSet<PathBuf>,
+    buffer: Vec<u8>,
+}
+
+impl InotifyWatcher {
+    pub fn new(file_types: Vec<String>, filter: Filter) -> CommonResult<Self> {
+        Ok(InotifyWatcher {
+            file_types,
+            file_notify: Inotify::init()?,
+            filter,
+            roots: vec![],
+            dirs: Default::default(),
+            files: Default::default(),
+            buffer: vec![0u8; 4096],
+        })
+    }
+    // 类型匹配并且没有被过滤, 目录已经在 add_dir 时过滤过
+    // 用 poll(2) 等待 inotify 可读, 超时或者被信号打断时返回 false
+    fn readable(&self, timeout: Duration) -> CommonResult<bool> {
+        let mut fd = libc::pollfd {
+            fd: self.file_notify.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+        match unsafe { libc::poll(&mut fd, 1, ms) } {
+            n if n >= 0 => Ok(n > 0),
+            _ => match io::Error::last_os_error() {
+                e if e.kind() == io::ErrorKind::Interrupted => Ok(false),
+                e => Err(e.into()),
+            },
+        }
+    }
+    fn is_target(&self, path: &Path) -> bool {
+        is_watched(path, &self.file_types)
+            && match root_of(&self.roots, path) {
+                Some(root) => !self.filter.is_ignored(root, path),
+                None => true,
+            }
+    }
+    // 监听目录及其所有子目录, 返回新加入的文件
+    fn add_dir(&mut self, path: &Path) -> CommonResult<Vec<WatchEvent>> {
+        let root = match root_of(&self.roots, path) {
+            Some(r) => r.to_path_buf(),
+            None => return Ok(vec![]),
+        };
+        let (dirs, file_dirs) = walk_dir(&root, path, &self.filter)?;
+        for dir in dirs {
+            let added = self.file_notify.add_watch(
+                &dir,
+                WatchMask::MODIFY
+                    | WatchMask::CREATE
+                    | WatchMask::DELETE
+                    | WatchMask::MOVED_FROM
+                    | WatchMask::MOVED_TO,
+            );
+            let wd = match added {
+                Ok(wd) => wd,
+                // 遍历之后目录已经被删除, 删除事件会在之后读到
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            self.dirs.insert(wd, PathBuf::from(dir));
+        }
+        let mut rsl = vec![];
+        for (name, dirs) in file_dirs {
+            for dir in dirs {
+                let path = Path::new(&dir).join(&name);
+                if self.is_target(&path) && self.files.insert(path.clone()) {
+                    rsl.push(WatchEvent::new(path, EventKind::Create));
+                }
+            }
+        }
+        Ok(rsl)
+    }
+    // 目录被删除或移走, 其中的文件都算删除
+    fn remove_dir(&mut self, path: &Path) -> Vec<WatchEvent> {
+        let wds = self
+            .dirs
+            .iter()
+            .filter(|(_, d)| d.starts_with(path))
+            .map(|(wd, _)| wd.clone())
+            .collect::<Vec<WatchDescriptor>>();
+        for wd in wds {
+            self.dirs.remove(&wd);
+            // 移走的目录内核不会移除监听, 删除的目录可能已经移除了, 忽略错误
+            let _ = self.file_notify.rm_watch(wd);
+        }
+        let mut removed = self
+            .files
+            .iter()
+            .filter(|f| f.starts_with(path))
+            .cloned()
+            .collect::<Vec<PathBuf>>();
+        removed.sort();
+        removed
+            .into_iter()
+            .map(|f| {
+                self.files.remove(&f);
+                WatchEvent::new(f, EventKind::Remove)
+            })
+            .collect()
+    }
+}
+
+impl Watcher for InotifyWatcher {
+    fn add(&mut self, path: &str) -> CommonResult<()> {
+        let path = fs::canonicalize(path)?;
+        self.roots.push(path.clone());
+        self.add_dir(&path)?;
+        Ok(())
+    }
+
+    fn wait(&mut self, timeout: Duration) -> CommonResult<Vec<WatchEvent>> {
+        let mut rsl = vec![];
+        if !self.readable(timeout)? {
+            return Ok(rsl);
+        }
+        let events = self
+            .file_notify
+            .read_events(&mut self.buffer)?
+            .map(|e| (e.wd, e.mask, e.name.map(PathBuf::from)))
+            .collect::<Vec<_>>();
+        for (wd, mask, name) in events {
+            // 目录被删除后内核会移除对应的监听
+            if mask.contains(EventMask::IGNORED) {
+                self.dirs.remove(&wd);
+                continue;
+            }
+            let path = match (self.dirs.get(&wd), name) {
+                (Some(dir), Some(name)) => dir.join(name),
+                _ => continue,
+            };
+            let removed = mask.intersects(EventMask::DELETE | EventMask::MOVED_FROM);
+            if mask.contains(EventMask::ISDIR) {
+                if removed {
+                    rsl.extend(self.remove_dir(&path));
+                } else if mask.intersects(EventMask::CREATE | EventMask::MOVED_TO) {
+                    rsl.extend(self.add_dir(&path)?);
+                }
+                continue;
+            }
+            if !self.is_target(&path) {
+                continue;
+            }
+            let kind = if removed {
+                if !self.files.remove(&path) {
+                    continue;
+                }
+                EventKind::Remove
+            } else if self.files.insert(path.clone()) {
+                EventKind::Create
+            } else {
+                EventKind::Modify
+            };
+            rsl.push(WatchEvent::new(path, kind));
+        }
+        Ok(rsl)
+    }
+}
+
+#[test]
+fn test_inotify_watcher() {
+    // 一直读到期望的事件出现
+    fn wait_for(w: &mut InotifyWatcher, seen: &mut Vec<WatchEvent>, expect: WatchEvent) {
+        for _ in 0..10 {
+            if let Some(p) = seen.iter().position(|e| *e == expect) {
+                seen.remove(p);
+                return;
+            }
+            seen.extend(w.wait(Duration::from_secs(1)).unwrap());
+        }
+        panic!("{:?} not received", expect);
+    }
+    let dir = std::env::temp_dir().join("fcss_test_inotify");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let dir = fs::canonicalize(&dir).unwrap();
+    let file = dir.join("a.vue");
+    fs::write(&file, "<template></template>").unwrap();
+    let mut w = InotifyWatcher::new(vec!["vue".to_string()], Filter::default()).unwrap();
+    w.add(dir.to_str().unwrap()).unwrap();
+    // 没有事件时等到超时
+    let start = std::time::Instant::now();
+    assert!(w.wait(Duration::from_millis(100)).unwrap().is_empty());
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    let mut seen = vec![];
+    fs::write(&file, "<template><div></div></template>").unwrap();
+    wait_for(
+        &mut w,
+        &mut seen,
+        WatchEvent::new(file.clone(), EventKind::Modify),
+    );
+    // 新建的目录也会被监听
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    let sub_file = dir.join("sub").join("b.vue");
+    fs::write(&sub_file, "<template></template>").unwrap();
+    wait_for(
+        &mut w,
+        &mut seen,
+        WatchEvent::new(sub_file.clone(), EventKind::Create),
+    );
+    // 不同目录下的同名文件互不干扰
+    let same_name = dir.join("sub").join("a.vue");
+    fs::write(&same_name, "<template></template>").unwrap();
+    wait_for(
+        &mut w,
+        &mut seen,
+        WatchEvent::new(same_name.clone(), EventKind::Create),
+    );
+    let moved = dir.join("sub").join("c.vue");
+    fs::rename(&sub_file, &moved).unwrap();
+    wait_for(
+        &mut w,
+        &mut seen,
+        WatchEvent::new(sub_file, EventKind::Remove),
+    );
+    wait_for(
+        &mut w,
+        &mut seen,
+        WatchEvent::new(moved.clone(), EventKind::Create),
+    );
+    fs::remove_file(&file).unwrap();
+    wait_for(&mut w, &mut seen, WatchEvent::new(file, EventKind::Remove));
+    // 读到新建事件时目录已经被删除
+    fs::create_dir_all(dir.join("tmp").join("x")).unwrap();
+    fs::remove_dir_all(dir.join("tmp")).unwrap();
+    seen.extend(w.wait(Duration::from_millis(100)).unwrap());
+    assert!(w.dirs.values().all(|d| !d.starts_with(dir.join("tmp"))));
+    // 移出监听目录后不再监听
+    fs::create_dir_all(dir.join("out")).unwrap();
+    let out = std::env::temp_dir().join("fcss_test_inotify_out");
+    let _ = fs::remove_dir_all(&out);
+    let out_file = dir.join("out").join("d.vue");
+    fs::write(&out_file, "").unwrap();
+    wait_for(
+        &mut w,
+        &mut seen,
+        WatchEvent::new(out_file.clone(), EventKind::Create),
+    );
+    let wd = w
+        .dirs
+        .iter()
+        .find(|(_, d)| **d == dir.join("out"))
+        .map(|(wd, _)| wd.clone())
+        .unwrap();
+    fs::rename(dir.join("out"), &out).unwrap();
+    wait_for(
+        &mut w,
+        &mut seen,
+        WatchEvent::new(out_file, EventKind::Remove),
+    );
+    assert!(w.file_notify.rm_watch(wd).is_err());
+    fs::remove_dir_all(&out).unwrap();
+    fs::remove_dir_all(dir.join("sub")).unwrap();
+    wait_for(&mut w, &mut seen, WatchEvent::new(moved, EventKind::Remove));
+    wait_for(
+        &mut w,
+        &mut seen,
+        WatchEvent::new(same_name, EventKind::Remove),
+    );
+    assert!(w.dirs.values().all(|d| !d.starts_with(dir.join("sub"))));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -1 +1,4 @@
-pub mod watch;
\ No newline at end of file
+#[cfg(target_os = "linux")]
+pub mod inotify;
+pub mod poll;
+pub mod watch;
@@ -0,0 +1,135 @@
+use crate::pkg::dir::walk_dir;
+use crate::pkg::ignore::Filter;
+use crate::pkg::result::CommonResult;
+use crate::watch::watch::{is_watched, EventKind, WatchEvent, Watcher};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::{Duration, Instant, SystemTime};
+
+// 定时扫描目录, 对比文件的修改时间和大小, 不依赖系统的文件通知
+pub struct PollWatcher {
+    file_types: Vec<String>,
+    interval: Duration,
+    filter: Filter,
+    dirs: Vec<PathBuf>,
+    files: HashMap<PathBuf, (SystemTime, u64)>,
+}
+
+impl PollWatcher {
+    pub fn new(file_types: Vec<String>, interval: Duration, filter: Filter) -> Self {
+        PollWatcher {
+            file_types,
+            interval,
+            filter,
+            dirs: vec![],
+            files: Default::default(),
+        }
+    }
+    fn scan(&self, dir: &Path) -> CommonResult<HashMap<PathBuf, (SystemTime, u64)>> {
+        let (_, file_dirs) = walk_dir(dir, dir, &self.filter)?;
+        let mut rsl = HashMap::new();
+        for (name, dirs) in file_dirs {
+            if !is_watched(Path::new(&name), &self.file_types) {
+                continue;
+            }
+            for d in dirs {
+                let full_path = Path::new(&d).join(&name);
+                // 扫描过程中被删除的文件忽略
+                if let Ok(meta) = full_path.metadata() {
+                    rsl.insert(full_path, (meta.modified()?, meta.len()));
+                }
+            }
+        }
+        Ok(rsl)
+    }
+    // 扫描一次, 返回新增, 修改和删除的文件
+    pub fn poll(&mut self) -> CommonResult<Vec<WatchEvent>> {
+        let mut current = HashMap::new();
+        for dir in &self.dirs {
+            current.extend(self.scan(dir)?);
+        }
+        let mut rsl = vec![];
+        for (p, stat) in current.iter() {
+            match self.files.get(p) {
+                None => rsl.push(WatchEvent::new(p.clone(), EventKind::Create)),
+                Some(old) if old != stat => rsl.push(WatchEvent::new(p.clone(), EventKind::Modify)),
+                _ => {}
+            }
+        }
+        for p in self.files.keys() {
+            if !current.contains_key(p) {
+                rsl.push(WatchEvent::new(p.clone(), EventKind::Remove));
+            }
+        }
+        rsl.sort();
+        self.files = current;
+        Ok(rsl)
+    }
+}
+
+impl Watcher for PollWatcher {
+    fn add(&mut self, path: &str) -> CommonResult<()> {
+        let path = fs::canonicalize(path)?;
+        let files = self.scan(&path)?;
+        self.files.extend(files);
+        self.dirs.push(path);
+        Ok(())
+    }
+
+    fn wait(&mut self, timeout: Duration) -> CommonResult<Vec<WatchEvent>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            sleep(self.interval);
+            let changed = self.poll()?;
+            if !changed.is_empty() || Instant::now() >= deadline {
+                return Ok(changed);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_poll_watcher() {
+    let dir = std::env::temp_dir().join("fcss_test_poll");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    let dir = std::fs::canonicalize(&dir).unwrap();
+    let a = dir.join("a.vue");
+    let b = dir.join("sub").join("b.vue");
+    std::fs::write(&a, "<template></template>").unwrap();
+    std::fs::write(dir.join("c.js"), "").unwrap();
+    let mut w = PollWatcher::new(
+        vec!["vue".to_string()],
+        Duration::from_millis(10),
+        Filter::default(),
+    );
+    w.add(dir.to_str().unwrap()).unwrap();
+    assert!(w.poll().unwrap().is_empty());
+    // 扫描时已经被删除的目录当作空目录
+    assert!(w.scan(&dir.join("missing")).unwrap().is_empty());
+    std::fs::write(&b, "<template></template>").unwrap();
+    std::fs::write(dir.join("c.js"), "changed").unwrap();
+    assert_eq!(
+        w.poll().unwrap(),
+        vec![WatchEvent::new(b.clone(), EventKind::Create)]
+    );
+    std::fs::write(&a, "<template><div></div></template>").unwrap();
+    assert_eq!(
+        w.wait(Duration::from_secs(5)).unwrap(),
+        vec![WatchEvent::new(a, EventKind::Modify)]
+    );
+    // 新建的目录不需要重新添加
+    std::fs::create_dir_all(dir.join("new")).unwrap();
+    let c = dir.join("new").join("c.vue");
+    std::fs::rename(&b, &c).unwrap();
+    assert_eq!(
+        w.poll().unwrap(),
+        vec![
+            WatchEvent::new(c, EventKind::Create),
+            WatchEvent::new(b, EventKind::Remove)
+        ]
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,168 +1,346 @@
-use crate::pkg::dir::walk_all_dir;
+use crate::pkg::ignore::Filter;
 use crate::pkg::result::CommonResult;
-use inotify::{EventMask, Inotify, WatchMask};
-use nom::dbg_dmp;
-use nom::lib::std::iter::FilterMap;
-use std::cell::RefCell;
-use std::collections::hash_map::RandomState;
-use std::collections::{HashMap, HashSet};
-use std::fmt::Debug;
-use std::fs::{read_dir, File, FileType, Metadata};
-use std::hash::Hash;
-use std::ops::Deref;
-use std::path::Path;
+#[cfg(target_os = "linux")]
+use crate::watch::inotify::InotifyWatcher;
+use crate::watch::poll::PollWatcher;
+use serde_derive::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
-use std::sync::{Arc, Mutex};
-use std::thread::{sleep, spawn};
-use std::time::{Duration, SystemTime};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::spawn;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize)]
+pub enum EventKind {
+    Create,
+    Modify,
+    Remove,
+}
+
+// path 为绝对路径
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize)]
+pub struct WatchEvent {
+    pub path: PathBuf,
+    pub kind: EventKind,
+}
+
+impl WatchEvent {
+    pub fn new(path: PathBuf, kind: EventKind) -> Self {
+        WatchEvent { path, kind }
+    }
+}
+
+// 编辑器保存时产生的备份和锁文件
+pub fn is_temp_file(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    name.ends_with('~') || name.starts_with(".#")
+}
+
+// 扩展名在 file_types 中, 并且不是临时文件
+pub fn is_watched(path: &Path, file_types: &[String]) -> bool {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_string())
+        .unwrap_or_default();
+    !is_temp_file(path) && file_types.contains(&ext)
+}
+
+// 文件监听的后端, inotify 只能在 linux 上用, 其他平台用轮询
+pub trait Watcher: Send {
+    fn add(&mut self, path: &str) -> CommonResult<()>;
+    // 阻塞直到有文件变化或者超过 timeout, 返回变化的文件, 重命名是旧路径的 Remove 加新路径的 Create
+    fn wait(&mut self, timeout: Duration) -> CommonResult<Vec<WatchEvent>>;
+}
+
+// name 为空时使用当前平台默认的后端
+pub fn backend(
+    name: &str,
+    file_types: &[String],
+    poll_interval: u64,
+    filter: Filter,
+) -> CommonResult<Box<dyn Watcher>> {
+    match name {
+        #[cfg(target_os = "linux")]
+        "" | "inotify" => Ok(Box::new(InotifyWatcher::new(file_types.to_vec(), filter)?)),
+        #[cfg(not(target_os = "linux"))]
+        "" => Ok(Box::new(PollWatcher::new(
+            file_types.to_vec(),
+            Duration::from_millis(poll_interval),
+            filter,
+        ))),
+        "poll" => Ok(Box::new(PollWatcher::new(
+            file_types.to_vec(),
+            Duration::from_millis(poll_interval),
+            filter,
+        ))),
+        _ => Err(Box::from(format!("unsupported watcher {}", name))),
+    }
+}
+
+// 同一路径在窗口内的多次变化合并为一次
+#[derive(Default)]
+struct Pending {
+    events: HashMap<PathBuf, (EventKind, Instant)>,
+    closed: bool,
+}
+
+impl Pending {
+    fn push(&mut self, e: WatchEvent) {
+        use EventKind::*;
+        let kind = match self.events.get(&e.path).map(|(k, _)| *k) {
+            // 窗口内新建又删除, 相当于没有变化
+            Some(Create) if e.kind == Remove => {
+                self.events.remove(&e.path);
+                return;
+            }
+            Some(Create) => Create,
+            Some(Remove) if e.kind != Remove => Modify,
+            _ => e.kind,
+        };
+        self.events.insert(e.path, (kind, Instant::now()));
+    }
+    // 取出已经超过窗口的事件
+    fn take(&mut self, debounce: Duration) -> Vec<WatchEvent> {
+        let now = Instant::now();
+        let due = self
+            .events
+            .iter()
+            .filter(|(_, (_, t))| now.duration_since(*t) >= debounce)
+            .map(|(p, _)| p.clone())
+            .collect::<Vec<PathBuf>>();
+        let mut rsl = due
+            .into_iter()
+            .filter_map(|p| self.events.remove(&p).map(|(k, _)| WatchEvent::new(p, k)))
+            .collect::<Vec<WatchEvent>>();
+        rsl.sort();
+        rsl
+    }
+    // 距离最早一个事件到期还要多久
+    fn next_due(&self, debounce: Duration) -> Option<Duration> {
+        let now = Instant::now();
+        self.events
+            .values()
+            .map(|(_, t)| debounce.saturating_sub(now.duration_since(*t)))
+            .min()
+    }
+}
+
+// watch 每次最多等待这么久就释放 watcher, 让 add 可以执行
+const WAIT_TIMEOUT: Duration = Duration::from_millis(200);
 
 pub struct Watch {
-    file_type: String,
-    file_notify: Arc<Mutex<Inotify>>,
-    file_dirs: Arc<Mutex<HashMap<String, HashSet<String>>>>,
-    sender: SyncSender<String>,
-    pub receiver: Arc<Mutex<Receiver<String>>>,
+    watcher: Mutex<Box<dyn Watcher>>,
+    // 取 watcher 之前先取 turn, watch 释放 watcher 后等待中的 add 先执行
+    turn: Mutex<()>,
+    debounce: Duration,
+    pending: Arc<(Mutex<Pending>, Condvar)>,
+    sender: SyncSender<WatchEvent>,
+    pub receiver: Arc<Mutex<Receiver<WatchEvent>>>,
 }
 
 impl Watch {
-    pub fn new(t: String) -> Self {
-        let (sender, receiver) = sync_channel::<String>(10);
+    pub fn new(watcher: Box<dyn Watcher>, debounce: Duration) -> Self {
+        let (sender, receiver) = sync_channel::<WatchEvent>(10);
         Watch {
-            file_type: t,
-            file_notify: Arc::new(Mutex::new(Inotify::init().unwrap())),
-            file_dirs: Default::default(),
+            watcher: Mutex::new(watcher),
+            turn: Default::default(),
+            debounce,
+            pending: Default::default(),
             sender,
             receiver: Arc::new(Mutex::new(receiver)),
         }
     }
-    pub fn add(&self, path: String) {
-        let (_, file_dirs) = walk_all_dir(&path).unwrap();
-        let mut each_dirs: HashSet<String> = Default::default();
-        for f_d in file_dirs {
-            let mut file_dirs = self.file_dirs.lock().unwrap();
-            match file_dirs.get_mut(&f_d.0) {
-                None => {
-                    if f_d.0.ends_with(&self.file_type) {
-                        println!("Add file {} to watch Success", f_d.0.clone());
-                        let mut set = HashSet::new();
-                        set.extend(f_d.1.clone());
-                        each_dirs.extend(f_d.1);
-                        file_dirs.insert(f_d.0, set);
-                    }
-                }
-                Some(d) => {
-                    d.extend(f_d.1);
-                }
+    pub fn add(&self, path: String) -> CommonResult<()> {
+        let _turn = self.turn.lock().unwrap();
+        self.watcher.lock().unwrap().add(&path)
+    }
+
+    // 读取事件的线程只写入 pending, 由单独的线程到期后发送, 接收方处理慢时不会阻塞读取
+    pub fn watch(&self) -> CommonResult<()> {
+        let pending = self.pending.clone();
+        let sender = self.sender.clone();
+        let debounce = self.debounce;
+        spawn(move || flush(&pending, debounce, sender));
+        let (lock, cvar) = &*self.pending;
+        let rsl = loop {
+            let mut watcher = {
+                let _turn = self.turn.lock().unwrap();
+                self.watcher.lock().unwrap()
             };
-        }
-        let mut file_notify = self.file_notify.lock().unwrap();
-        for each_dir in each_dirs {
-            file_notify
-                .add_watch(&each_dir, WatchMask::MODIFY | WatchMask::CREATE)
-                .expect("Failed to add inotify watch");
-            println!("Add dir {} to watch Success", each_dir)
-        }
+            let waited = watcher.wait(WAIT_TIMEOUT);
+            drop(watcher);
+            let events = match waited {
+                Ok(events) => events,
+                Err(e) => break Err(e),
+            };
+            let mut state = lock.lock().unwrap();
+            if state.closed {
+                break Ok(());
+            }
+            for e in events {
+                state.push(e);
+            }
+            cvar.notify_one();
+        };
+        lock.lock().unwrap().closed = true;
+        cvar.notify_one();
+        rsl
     }
+}
 
-    pub fn watch(&self) {
-        let mut buffer = [0u8; 4096];
-        loop {
-            let events = self
-                .file_notify
-                .lock()
-                .unwrap()
-                .read_events_blocking(&mut buffer)
-                .expect("Read File Events Error");
-            for event in events {
-                if event.mask.contains(EventMask::CREATE) || event.mask.contains(EventMask::MODIFY)
-                {
-                    if !event.mask.contains(EventMask::ISDIR) {
-                        let mut file_dirs = self.file_dirs.lock().unwrap();
-                        let mut have_match = false;
-                        let mut walk_match = false;
-                        let watch_file_name = event.name.unwrap().to_str().unwrap().to_string();
-                        if watch_file_name.ends_with("~") {
-                            continue;
-                        }
-                        match file_dirs.get(&watch_file_name) {
-                            None => {}
-                            Some(d) => {
-                                for dir in d.iter() {
-                                    let full_path = Path::new(dir).join(watch_file_name.clone());
-                                    let m_time = full_path.metadata().unwrap().modified().unwrap();
-                                    if SystemTime::now()
-                                        .duration_since(m_time)
-                                        .unwrap()
-                                        .as_secs_f32()
-                                        < 1.0
-                                    {
-                                        have_match = true;
-                                        self.sender.send(full_path.to_str().unwrap().to_string());
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-                        let mut match_dir = "".to_string();
-                        // 没有匹配到,就遍历
-                        if !have_match {
-                            println!("触发全部遍历!");
-                            for (_, dirs) in file_dirs.iter() {
-                                for dir in dirs {
-                                    let full_path = Path::new(dir).join(watch_file_name.clone());
-                                    let m_time = full_path.metadata().unwrap().modified().unwrap();
-                                    if SystemTime::now()
-                                        .duration_since(m_time)
-                                        .unwrap()
-                                        .as_secs_f32()
-                                        < 1.0
-                                    {
-                                        have_match = true;
-                                        walk_match = true;
-                                        match_dir = dir.clone();
-                                        self.sender.send(full_path.to_str().unwrap().to_string());
-                                        break;
-                                    }
-                                }
-                                if have_match {
-                                    break;
-                                }
-                            }
-                        }
-                        if !have_match {
-                            panic!("未查找到该文件!")
-                        } else {
-                            if walk_match {
-                                match file_dirs.get_mut(&*watch_file_name) {
-                                    None => {
-                                        let mut set = HashSet::new();
-                                        set.insert(match_dir);
-                                        file_dirs.insert(watch_file_name, set);
-                                    }
-                                    Some(d) => {
-                                        d.insert(match_dir);
-                                    }
-                                }
-                                println!("该文件已增加处理!");
-                            }
-                        }
-                    }
-                }
+fn flush(pending: &(Mutex<Pending>, Condvar), debounce: Duration, sender: SyncSender<WatchEvent>) {
+    let (lock, cvar) = pending;
+    let mut state = lock.lock().unwrap();
+    loop {
+        if state.closed {
+            return;
+        }
+        let ready = state.take(debounce);
+        if ready.is_empty() {
+            state = match state.next_due(debounce) {
+                Some(d) => cvar.wait_timeout(state, d).unwrap().0,
+                None => cvar.wait(state).unwrap(),
+            };
+            continue;
+        }
+        drop(state);
+        for e in ready {
+            if sender.send(e).is_err() {
+                lock.lock().unwrap().closed = true;
+                return;
             }
         }
+        state = lock.lock().unwrap();
     }
 }
 
 #[test]
 fn test_watch() {
-    let w = Arc::new(Watch::new("vue".to_string()));
-    w.add("/home/jiuker/goworkspace/src/dmallRedisSync/center/res/vue-element-admin".to_string());
+    let dir = std::env::temp_dir().join("fcss_test_watch");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let dir = std::fs::canonicalize(&dir).unwrap();
+    let w = Arc::new(Watch::new(
+        backend(
+            "poll",
+            &["vue".to_string()],
+            10,
+            Filter::new(&[], &["ignored/".to_string()]).unwrap(),
+        )
+        .unwrap(),
+        Duration::from_millis(10),
+    ));
+    w.add(dir.to_str().unwrap().to_string()).unwrap();
+    let w_c = w.clone();
+    spawn(move || {
+        w_c.watch().unwrap();
+    });
+    // 被过滤的文件不会发送
+    std::fs::create_dir_all(dir.join("ignored")).unwrap();
+    std::fs::write(dir.join("ignored").join("b.vue"), "").unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    let file = dir.join("a.vue");
+    std::fs::write(&file, "<template></template>").unwrap();
+    let e = w.receiver.lock().unwrap().recv().unwrap();
+    assert_eq!(e, WatchEvent::new(file, EventKind::Create));
+    assert!(backend("fsevent", &["vue".to_string()], 10, Filter::default()).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_add_while_watching() {
+    let dir = std::env::temp_dir().join("fcss_test_watch_add");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("a")).unwrap();
+    std::fs::create_dir_all(dir.join("b")).unwrap();
+    let dir = std::fs::canonicalize(&dir).unwrap();
+    let w = Arc::new(Watch::new(
+        backend("", &["vue".to_string()], 10, Filter::default()).unwrap(),
+        Duration::from_millis(10),
+    ));
+    w.add(dir.join("a").to_str().unwrap().to_string()).unwrap();
+    let w_c = w.clone();
+    spawn(move || w_c.watch().unwrap());
+    std::thread::sleep(Duration::from_millis(50));
+    // watch 阻塞等待时 add 不能一直等下去
+    let (done, added) = sync_channel(1);
     let w_c = w.clone();
+    let b = dir.join("b");
     spawn(move || {
-        w_c.watch();
+        done.send(w_c.add(b.to_str().unwrap().to_string()).is_ok())
+            .unwrap()
     });
-    while let Ok(p) = w.receiver.lock().unwrap().recv() {
-        println!("{}", p);
-        break;
+    assert!(added.recv_timeout(Duration::from_secs(5)).unwrap());
+    let file = dir.join("b").join("c.vue");
+    std::fs::write(&file, "<template></template>").unwrap();
+    let e = w
+        .receiver
+        .lock()
+        .unwrap()
+        .recv_timeout(Duration::from_secs(5))
+        .unwrap();
+    assert_eq!(e, WatchEvent::new(file, EventKind::Create));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_debounce() {
+    // 按顺序返回事先准备好的事件
+    struct Script(Vec<Vec<WatchEvent>>);
+    impl Watcher for Script {
+        fn add(&mut self, _: &str) -> CommonResult<()> {
+            Ok(())
+        }
+        fn wait(&mut self, _: Duration) -> CommonResult<Vec<WatchEvent>> {
+            match self.0.pop() {
+                Some(events) => Ok(events),
+                None => {
+                    std::thread::sleep(Duration::from_millis(10));
+                    Ok(vec![])
+                }
+            }
+        }
+    }
+    let event = |p: &str, kind| WatchEvent::new(PathBuf::from(p), kind);
+    let mut script = vec![
+        vec![event("/a.vue", EventKind::Modify)],
+        vec![
+            event("/a.vue", EventKind::Modify),
+            event("/b.vue", EventKind::Create),
+        ],
+        vec![
+            event("/b.vue", EventKind::Modify),
+            event("/c.vue", EventKind::Create),
+            event("/d.vue", EventKind::Remove),
+        ],
+        vec![
+            event("/c.vue", EventKind::Remove),
+            event("/d.vue", EventKind::Create),
+        ],
+    ];
+    script.reverse();
+    let w = Arc::new(Watch::new(
+        Box::new(Script(script)),
+        Duration::from_millis(100),
+    ));
+    let w_c = w.clone();
+    spawn(move || w_c.watch().unwrap());
+    let receiver = w.receiver.lock().unwrap();
+    let mut received = vec![];
+    while let Ok(e) = receiver.recv_timeout(Duration::from_millis(500)) {
+        received.push(e);
     }
+    received.sort();
+    assert_eq!(
+        received,
+        vec![
+            event("/a.vue", EventKind::Modify),
+            event("/b.vue", EventKind::Create),
+            event("/d.vue", EventKind::Modify),
+        ]
+    );
 }